@@ -0,0 +1,150 @@
+pub mod add;
+pub mod agent;
+pub mod batch;
+pub mod config;
+pub mod copy;
+pub mod delete;
+pub mod edit;
+pub mod encrypt;
+pub mod history;
+pub mod log;
+pub mod merge;
+pub mod move_tag;
+pub mod rename;
+pub mod run;
+pub mod show;
+pub mod sync;
+pub mod undo;
+#[cfg(test)]
+#[path = "repo/unittest_repository.rs"]
+pub mod unittest_repository;
+pub mod update;
+
+pub use tagcm::cmd::{risk, search, secret, tag, OutputFormat, ALL_SUBCOMMAND};
+
+use crate::config::SecretAction;
+use anyhow::{bail, Result};
+use search::TagData;
+use secret::SecretRules;
+use std::io::{self, IsTerminal};
+
+/// Asks `question` on the terminal, true when the answer is `y`.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    println!("{} [y/n]", question);
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim() == "y")
+}
+
+/// Lets `entries` through to `action` (e.g. `run`) when none of them is risky, with
+/// `assume_yes`, or when the user agrees on the terminal. Without a terminal it refuses.
+pub fn approve(entries: &[TagData], action: &str, assume_yes: bool) -> Result<bool> {
+    let risky = risk::risky(entries);
+    if risky.is_empty() || assume_yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        bail!(
+            "{} looks dangerous, use --yes to {} it",
+            risky.join(", "),
+            action
+        );
+    }
+    confirm(&format!(
+        "{} looks dangerous, {} anyway?",
+        risky.join(", "),
+        action
+    ))
+    .map_err(Into::into)
+}
+
+/// The command to store under `tag`: `command` as is when it has no secrets, otherwise
+/// what `action` makes of it. `ask` asks on the terminal and only warns without one.
+pub fn screen(
+    rules: &SecretRules,
+    tag: &str,
+    command: &str,
+    action: SecretAction,
+) -> Result<String> {
+    let secrets = rules.find(command);
+    if secrets.is_empty() {
+        return Ok(command.to_string());
+    }
+    let (found, references) = secret::describe(&secrets);
+    let redact = match action {
+        SecretAction::Refuse => bail!(
+            "{} contains a secret ({}), use --redact to replace it with {} or --keep-secrets to store it",
+            tag,
+            found,
+            references
+        ),
+        SecretAction::Redact => true,
+        SecretAction::Warn => false,
+        SecretAction::Ask if !io::stdin().is_terminal() => false,
+        SecretAction::Ask => confirm(&format!(
+            "{} contains a secret ({}), replace it with {}?",
+            tag, found, references
+        ))?,
+    };
+    if redact {
+        eprintln!(
+            "replaced the secret in {} with {}, set it in the environment before running it",
+            tag, references
+        );
+        Ok(rules.redacted(command))
+    } else {
+        eprintln!(
+            "warning: {} contains a secret ({}), it is stored as is",
+            tag, found
+        );
+        Ok(command.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use risk::RiskRules;
+
+    #[test]
+    fn test_approve() {
+        let mut entries = vec![TagData::new("ls".to_string(), "ls".to_string())];
+        RiskRules::default().mark(&mut entries);
+        assert!(approve(&entries, "run", false).unwrap());
+        entries.push(TagData::new("wipe".to_string(), "rm -rf /".to_string()));
+        RiskRules::default().mark(&mut entries);
+        assert!(approve(&entries, "run", true).unwrap());
+    }
+
+    #[test]
+    fn test_screen() {
+        let rules = SecretRules::default();
+        let command = "TOKEN=abc make";
+        assert_eq!(
+            screen(&rules, "t", "make", SecretAction::Refuse).unwrap(),
+            "make"
+        );
+        assert_eq!(
+            screen(&rules, "t", command, SecretAction::Warn).unwrap(),
+            command
+        );
+        assert_eq!(
+            screen(&rules, "t", command, SecretAction::Redact).unwrap(),
+            "TOKEN=${TOKEN} make"
+        );
+        assert_eq!(
+            screen(
+                &rules,
+                "t",
+                "export API_TOKEN='abc' && make",
+                SecretAction::Redact
+            )
+            .unwrap(),
+            "export API_TOKEN=\"${API_TOKEN}\" && make"
+        );
+        let error = screen(&rules, "t", command, SecretAction::Refuse).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("t contains a secret (secret variable)"));
+    }
+}
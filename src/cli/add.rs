@@ -0,0 +1,276 @@
+use crate::repo::journal::{Change, Journal};
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::{anyhow, Result};
+use crossterm::style::Stylize;
+use std::io;
+
+use super::{edit, tag};
+
+/// Stores `command` under `tag`, which must follow the tag grammar of `tag::validate`.
+/// A different command already stored under `tag` in the store changes go to is only
+/// replaced with `force`, the old one stays in the journal. Returns the layer whose
+/// entry still hides the new one, if any.
+pub fn add<T: TagDataRepository + ?Sized>(
+    tag: String,
+    command: String,
+    repo: &mut T,
+    journal: &Journal,
+    force: bool,
+) -> Result<Option<String>, RepoError> {
+    tag::validate(&tag)?;
+    match repo.writable_data().remove(&tag) {
+        Some(old) if old == command => return Ok(hidden_by(repo, &tag, &command)),
+        Some(_) if !force => return Err(RepoError::AlreadyExists(tag)),
+        _ => {}
+    }
+    journal.apply(
+        repo,
+        &[Change::Add {
+            tag: tag.clone(),
+            command: command.clone(),
+        }],
+    )?;
+    Ok(hidden_by(repo, &tag, &command))
+}
+
+/// The command to add under `tag`: `command` when given, else what is saved in the editor
+/// with `edit` or read from standard input. `None` when the editor was emptied.
+pub fn command_to_add<T: TagDataRepository + ?Sized>(
+    repo: &T,
+    tag: &str,
+    command: Option<String>,
+    edit: bool,
+) -> Result<Option<String>> {
+    match (command, edit) {
+        (Some(command), _) => Ok(Some(command)),
+        (None, true) => {
+            tag::validate(tag)?;
+            let current = repo.get_tag_data(tag).unwrap_or_default();
+            edit::edit_command(tag, repo.get_origin(tag).as_deref(), &current)
+        }
+        (None, false) => edit::command_from(&io::read_to_string(io::stdin())?)
+            .ok_or_else(|| anyhow!("no command on standard input"))
+            .map(Some),
+    }
+}
+
+fn hidden_by<T: TagDataRepository + ?Sized>(repo: &T, tag: &str, command: &str) -> Option<String> {
+    match repo.get_tag_data(tag) {
+        Some(current) if current != command => repo.get_origin(tag),
+        _ => None,
+    }
+}
+
+// runs of spaces and of everything else, so the diff keeps the spacing.
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if let Some(&(i, next)) = chars.peek() {
+            if c.is_whitespace() != next.is_whitespace() {
+                tokens.push(&text[start..i]);
+                start = i;
+            }
+        }
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// turns `old` into `new` keeping their longest common subsequence.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Removed(old[i]));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(new[j]));
+            j += 1;
+        }
+    }
+    edits
+}
+
+/// The `old` and the `new` command as a `-` and a `+` line, the words that differ
+/// colored when `color` is set. Multi-line commands are compared line by line.
+pub fn diff(old: &str, new: &str, color: bool) -> String {
+    if old.contains('\n') || new.contains('\n') {
+        let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+        return edits(&old, &new)
+            .into_iter()
+            .map(|edit| match (edit, color) {
+                (Edit::Same(line), _) => format!("  {}\n", line),
+                (Edit::Removed(line), true) => format!("{}\n", format!("- {}", line).red()),
+                (Edit::Removed(line), false) => format!("- {}\n", line),
+                (Edit::Added(line), true) => format!("{}\n", format!("+ {}", line).green()),
+                (Edit::Added(line), false) => format!("+ {}\n", line),
+            })
+            .collect();
+    }
+
+    let (mut removed, mut added) = (String::from("- "), String::from("+ "));
+    for edit in edits(&tokens(old), &tokens(new)) {
+        match (edit, color) {
+            (Edit::Same(token), _) => {
+                removed.push_str(token);
+                added.push_str(token);
+            }
+            (Edit::Removed(token), true) => removed.push_str(&token.red().bold().to_string()),
+            (Edit::Removed(token), false) => removed.push_str(token),
+            (Edit::Added(token), true) => added.push_str(&token.green().bold().to_string()),
+            (Edit::Added(token), false) => added.push_str(token),
+        }
+    }
+    format!("{}\n{}\n", removed, added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    #[test]
+    fn test_add() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("add");
+        add(
+            "test".to_string(),
+            "echo add test".to_string(),
+            &mut repo,
+            &journal,
+            false,
+        )
+        .unwrap();
+        assert_eq!(repo.get_tag_data("test").unwrap(), "echo add test");
+        assert!(matches!(
+            add(
+                "all".to_string(),
+                "echo".to_string(),
+                &mut repo,
+                &journal,
+                false
+            ),
+            Err(RepoError::Reserved(_))
+        ));
+        assert!(matches!(
+            add(
+                "--help".to_string(),
+                "echo".to_string(),
+                &mut repo,
+                &journal,
+                false
+            ),
+            Err(RepoError::InvalidTag { .. })
+        ));
+        assert_eq!(journal.entries().unwrap().len(), 1);
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_add_existing() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("add_existing");
+        let mut add = |command: &str, force| {
+            add(
+                "test".to_string(),
+                command.to_string(),
+                &mut repo,
+                &journal,
+                force,
+            )
+        };
+        add("echo old", false).unwrap();
+        add("echo old", false).unwrap();
+        assert!(matches!(
+            add("echo new", false),
+            Err(RepoError::AlreadyExists(_))
+        ));
+        add("echo new", true).unwrap();
+        assert_eq!(repo.get_tag_data("test").unwrap(), "echo new");
+        // nothing is recorded for the add that changed nothing.
+        assert_eq!(journal.entries().unwrap().len(), 2);
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_add_below_a_layer() {
+        use crate::repo::journal::Operation;
+        use crate::repo::layered_repository::{LayeredRepository, GLOBAL_LAYER, TEAM_LAYER};
+        let mut team = UnitTestRepository::new();
+        team.add_tag_data("build".to_string(), "make".to_string())
+            .unwrap();
+        let mut repo = LayeredRepository::new();
+        repo.add_layer(TEAM_LAYER, Box::new(team));
+        repo.add_layer(GLOBAL_LAYER, Box::new(UnitTestRepository::new()));
+        repo.set_write_layer(GLOBAL_LAYER).unwrap();
+        let journal = temporary_journal("add_below_a_layer");
+
+        let hidden = add(
+            "build".to_string(),
+            "cargo build".to_string(),
+            &mut repo,
+            &journal,
+            false,
+        )
+        .unwrap();
+        assert_eq!(hidden.as_deref(), Some(TEAM_LAYER));
+        assert_eq!(repo.get_tag_data("build").unwrap(), "make");
+        let entries = journal.entries().unwrap();
+        assert!(matches!(
+            entries[0].ops(),
+            [Operation::Add { tag, .. }] if tag == "build"
+        ));
+
+        journal.undo(&mut repo).unwrap();
+        assert!(repo.writable_data().is_empty());
+        assert_eq!(repo.get_tag_data("build").unwrap(), "make");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff("echo  hello world", "echo  hello there world", false),
+            "- echo  hello world\n+ echo  hello there world\n"
+        );
+        assert_eq!(tokens("a  b c"), vec!["a", "  ", "b", " ", "c"]);
+        assert_eq!(tokens(""), Vec::<&str>::new());
+        let colored = diff("make build", "make test", true);
+        assert!(colored.contains(&"build".red().bold().to_string()));
+        assert!(colored.contains(&"test".green().bold().to_string()));
+        assert!(colored.starts_with("- make "));
+
+        assert_eq!(
+            diff(
+                "set -e\nmake\nmake test",
+                "set -e\nmake build\nmake test",
+                false
+            ),
+            "  set -e\n- make\n+ make build\n  make test\n"
+        );
+    }
+}
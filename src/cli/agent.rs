@@ -0,0 +1,184 @@
+use crate::repo::encrypted_repository::{self, EncryptedRepository};
+use crate::repo::passphrase;
+use crate::repo::tag_data_repository::TagDataRepository;
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn socket() -> Result<PathBuf> {
+    passphrase::agent_socket().ok_or_else(|| {
+        anyhow!(
+            "no cache directory for the agent, set {}",
+            passphrase::ENV_AGENT_SOCKET
+        )
+    })
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use anyhow::bail;
+    use std::env;
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::process::{Command, Stdio};
+    use std::thread::sleep;
+    use std::time::Instant;
+
+    const POLL: Duration = Duration::from_millis(100);
+
+    // sends `request` to the running agent, `None` when there is none.
+    fn ask(request: &str) -> Option<String> {
+        let mut stream = UnixStream::connect(socket().ok()?).ok()?;
+        stream.write_all(format!("{}\n", request).as_bytes()).ok()?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer).ok()?;
+        Some(answer.trim_end().to_string())
+    }
+
+    /// Keeps `passphrase` in a background process for `timeout`, so commands on
+    /// encrypted stores find it there instead of asking.
+    pub fn start(passphrase: &str, timeout: Duration) -> Result<()> {
+        if ask("status").is_some() {
+            bail!("the agent is already running, stop it with `tagcm agent stop`");
+        }
+        let socket = socket()?;
+        let mut child = Command::new(env::current_exe()?)
+            .args(["agent", "serve", "--timeout-secs"])
+            .arg(timeout.as_secs().to_string())
+            .env(passphrase::ENV_AGENT_SOCKET, &socket)
+            .env_remove(passphrase::ENV_PASSPHRASE)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // the passphrase goes through a pipe, not the command line or the environment.
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(format!("{}\n", passphrase).as_bytes())?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if ask("status").is_some() {
+                return Ok(());
+            }
+            if let Some(status) = child.try_wait()? {
+                bail!("the agent stopped right away ({})", status);
+            }
+            sleep(POLL);
+        }
+        bail!("the agent did not start listening on {}", socket.display())
+    }
+
+    /// Serves the passphrase read from stdin on the agent socket until `timeout` is
+    /// over or it is told to stop. `start` runs it in the background.
+    pub fn serve(timeout: Duration) -> Result<()> {
+        let mut passphrase = String::new();
+        io::stdin().read_line(&mut passphrase)?;
+        let passphrase = passphrase.trim_end_matches('\n');
+        let socket = socket()?;
+        if let Some(parent) = socket.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // a socket left by an agent that did not stop cleanly.
+        _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+        fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            let mut stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    sleep(POLL);
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            stream.set_nonblocking(false)?;
+            stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let answer = match request.trim_end() {
+                "get" => passphrase.to_string(),
+                "status" => deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_secs()
+                    .to_string(),
+                "stop" => break,
+                _ => continue,
+            };
+            _ = writeln!(stream, "{}", answer);
+        }
+        _ = fs::remove_file(&socket);
+        Ok(())
+    }
+
+    /// Stops the running agent, false when there was none.
+    pub fn stop() -> Result<bool> {
+        let running = ask("stop").is_some();
+        // it answers once it is gone.
+        while running && ask("status").is_some() {
+            sleep(POLL);
+        }
+        Ok(running)
+    }
+
+    /// How long the running agent keeps the passphrase, `None` when none is running.
+    pub fn status() -> Result<Option<Duration>> {
+        Ok(ask("status")
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs))
+    }
+}
+
+#[cfg(unix)]
+pub use unix::{serve, start, status, stop};
+
+#[cfg(not(unix))]
+mod other {
+    use super::*;
+    use anyhow::bail;
+
+    fn unsupported<T>() -> Result<T> {
+        bail!(
+            "the agent needs Unix domain sockets, set {} instead",
+            passphrase::ENV_PASSPHRASE
+        )
+    }
+
+    pub fn start(_: &str, _: Duration) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn serve(_: Duration) -> Result<()> {
+        unsupported()
+    }
+
+    pub fn stop() -> Result<bool> {
+        unsupported()
+    }
+
+    pub fn status() -> Result<Option<Duration>> {
+        unsupported()
+    }
+}
+
+#[cfg(not(unix))]
+pub use other::{serve, start, status, stop};
+
+/// `agent start`: asks for the passphrase of the store at `path` and keeps it for
+/// `timeout_minutes`. A mistyped one is caught now rather than by the next command.
+pub fn start_for_store(path: &str, timeout_minutes: u64) -> Result<()> {
+    let passphrase = passphrase::get(path)?;
+    if encrypted_repository::is_encrypted(path) {
+        EncryptedRepository::with_passphrase(&passphrase).init(path)?;
+    }
+    start(&passphrase, Duration::from_secs(timeout_minutes * 60))
+}
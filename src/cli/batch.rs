@@ -0,0 +1,258 @@
+use super::tag;
+use crate::repo::journal::{Change, Journal};
+use crate::repo::tag_data_repository::TagDataRepository;
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+
+/// One operation of a batch script.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    /// Line number in the script, from 1.
+    pub number: usize,
+    pub change: Change,
+    /// The line is a `replace`, an add allowed to replace an existing tag.
+    pub replace: bool,
+}
+
+/// Reads a batch script: one `add <tag> <command>`, `replace <tag> <command>`,
+/// `delete <tag>` or `rename <tag> <new tag>` per line, `#` starts a comment line.
+pub fn parse(script: &str) -> Result<Vec<Line>> {
+    let mut lines = Vec::new();
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = number + 1;
+        let (op, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let change = match (op, args.as_slice()) {
+            ("add" | "replace", [tag, _, ..]) => Change::Add {
+                tag: tag.to_string(),
+                // the command is the rest of the line, spacing kept.
+                command: rest.trim_start()[tag.len()..].trim().to_string(),
+            },
+            ("delete", [tag]) => Change::Delete {
+                tag: tag.to_string(),
+            },
+            ("rename", [tag, new_tag]) => Change::Rename {
+                tag: tag.to_string(),
+                new_tag: new_tag.to_string(),
+            },
+            ("add" | "replace", _) => {
+                bail!("line {}: expected `{} <tag> <command>`", number, op)
+            }
+            ("delete", _) => bail!("line {}: expected `delete <tag>`", number),
+            ("rename", _) => bail!("line {}: expected `rename <tag> <new tag>`", number),
+            _ => bail!("line {}: unknown operation {}", number, op),
+        };
+        if let Change::Add { tag, .. } | Change::Rename { new_tag: tag, .. } = &change {
+            if let Err(e) = tag::validate(tag) {
+                bail!("line {}: {}", number, e);
+            }
+        }
+        lines.push(Line {
+            number,
+            change,
+            replace: op == "replace",
+        });
+    }
+    Ok(lines)
+}
+
+/// Applies every operation of `script` to `repo` and saves it once, or applies none when one fails.
+/// An `add` of a tag that exists by then fails like `add` does, unless `force`.
+/// The command of every add goes through `screen` first, e.g. to take secrets out.
+/// Returns how many were applied.
+pub fn batch<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    script: &str,
+    force: bool,
+    screen: impl Fn(&str, &str) -> Result<String>,
+) -> Result<usize> {
+    let lines = parse(script)?;
+    let mut tags: HashSet<String> = repo.writable_data().into_keys().collect();
+    let mut changes = Vec::new();
+    for line in lines {
+        let mut change = line.change;
+        match &mut change {
+            Change::Add { tag, command } => {
+                if !tags.insert(tag.clone()) && !force && !line.replace {
+                    bail!(
+                        "line {}: tag {} already exists, use `replace` or --force to replace it",
+                        line.number,
+                        tag
+                    );
+                }
+                *command = screen(tag, command)?;
+            }
+            Change::Delete { tag } => {
+                tags.remove(tag);
+            }
+            Change::Rename { tag, new_tag } => {
+                tags.remove(tag);
+                tags.insert(new_tag.clone());
+            }
+        }
+        changes.push(change);
+    }
+    if !changes.is_empty() {
+        journal.apply(repo, &changes)?;
+    }
+    Ok(changes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+
+    #[test]
+    fn test_parse() {
+        let script =
+            "# setup\nadd build  cargo build --release\n\ndelete old\nrename a b\nreplace c ls\n";
+        let changes: Vec<Change> = parse(script)
+            .unwrap()
+            .into_iter()
+            .map(|line| line.change)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Add {
+                    tag: "build".to_string(),
+                    command: "cargo build --release".to_string(),
+                },
+                Change::Delete {
+                    tag: "old".to_string(),
+                },
+                Change::Rename {
+                    tag: "a".to_string(),
+                    new_tag: "b".to_string(),
+                },
+                Change::Add {
+                    tag: "c".to_string(),
+                    command: "ls".to_string(),
+                },
+            ]
+        );
+        let replace: Vec<bool> = parse(script).unwrap().iter().map(|l| l.replace).collect();
+        assert_eq!(replace, vec![false, false, false, true]);
+        let error = |script| parse(script).unwrap_err().to_string();
+        assert_eq!(
+            error("add a echo\nadd b"),
+            "line 2: expected `add <tag> <command>`"
+        );
+        assert_eq!(
+            error("replace a"),
+            "line 1: expected `replace <tag> <command>`"
+        );
+        assert_eq!(error("delete a b"), "line 1: expected `delete <tag>`");
+        assert_eq!(error("\n\ncopy a b"), "line 3: unknown operation copy");
+        assert_eq!(error("rename a all"), "line 1: tag all is reserved");
+        assert_eq!(
+            error("add -f echo"),
+            "line 1: invalid tag -f: -f starts with \"-\""
+        );
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("batch");
+        repo.add_tag_data("old".to_string(), "echo old".to_string())
+            .unwrap();
+        let keep = |_: &str, command: &str| Ok(command.to_string());
+        assert!(batch(
+            &mut repo,
+            &journal,
+            "add new echo new\ndelete missing",
+            false,
+            keep
+        )
+        .is_err());
+        assert_eq!(repo.get_tag_data("new"), None);
+
+        assert_eq!(
+            batch(
+                &mut repo,
+                &journal,
+                "add new echo new\ndelete old",
+                false,
+                keep
+            )
+            .unwrap(),
+            2
+        );
+        assert_eq!(repo.get_tag_data("new").unwrap(), "echo new");
+        assert_eq!(repo.get_tag_data("old"), None);
+        assert_eq!(journal.entries().unwrap().len(), 1);
+
+        let refuse = |tag: &str, _: &str| -> Result<String> { bail!("{} refused", tag) };
+        assert!(batch(&mut repo, &journal, "delete new\nadd x echo", false, refuse).is_err());
+        assert_eq!(repo.get_tag_data("new").unwrap(), "echo new");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_batch_existing_tag() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("batch-existing");
+        repo.add_tag_data("old".to_string(), "echo old".to_string())
+            .unwrap();
+        let keep = |_: &str, command: &str| Ok(command.to_string());
+        let error = batch(
+            &mut repo,
+            &journal,
+            "add new echo\nadd old echo new",
+            false,
+            keep,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2: tag old already exists, use `replace` or --force to replace it"
+        );
+        assert_eq!(repo.get_tag_data("new"), None);
+        assert_eq!(repo.get_tag_data("old").unwrap(), "echo old");
+        assert!(batch(
+            &mut repo,
+            &journal,
+            "add a echo\nadd a echo again",
+            false,
+            keep
+        )
+        .is_err());
+        assert_eq!(
+            batch(
+                &mut repo,
+                &journal,
+                "delete old\nadd old echo again",
+                false,
+                keep
+            )
+            .unwrap(),
+            2
+        );
+
+        assert_eq!(
+            batch(
+                &mut repo,
+                &journal,
+                "replace old echo replaced",
+                false,
+                keep
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(repo.get_tag_data("old").unwrap(), "echo replaced");
+        assert_eq!(
+            batch(&mut repo, &journal, "add old echo forced", true, keep).unwrap(),
+            1
+        );
+        assert_eq!(repo.get_tag_data("old").unwrap(), "echo forced");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+use crate::config::{self, Config};
+use anyhow::Result;
+use std::path::Path;
+use toml::Value;
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
+
+pub fn get(config: &Config, key: &str) -> Result<()> {
+    println!("{}", format_value(&config.get(key)?));
+    Ok(())
+}
+
+pub fn set(path: &Path, key: &str, value: &str) -> Result<()> {
+    config::set(path, key, value)?;
+    println!("{} = {}", key, value);
+    Ok(())
+}
+
+pub fn list(config: &Config) -> Result<()> {
+    for (key, value) in config.entries()? {
+        println!("{} = {}", key, value);
+    }
+    Ok(())
+}
+
+pub fn path(path: &Path) {
+    println!("{}", path.display());
+}
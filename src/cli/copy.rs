@@ -0,0 +1,76 @@
+use crate::repo::journal::{Change, Journal};
+use crate::repo::layered_repository::LayeredRepository;
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::Result;
+
+use super::tag;
+
+/// Stores the command of `src` under `dst` too, in the layer `src` comes from, where
+/// `dst` must not exist yet unless `force`. Returns that layer.
+pub fn copy(repo: &mut LayeredRepository, src: &str, dst: &str, force: bool) -> Result<String> {
+    let layer = repo
+        .layer_of(src)
+        .ok_or_else(|| RepoError::NotFound(src.to_string()))?;
+    let journal = repo.journal(&layer)?;
+    copy_in(repo.layer_mut(&layer)?, &journal, src, dst, force)?;
+    Ok(layer)
+}
+
+fn copy_in<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    src: &str,
+    dst: &str,
+    force: bool,
+) -> Result<(), RepoError> {
+    tag::validate(dst)?;
+    let command = repo
+        .get_tag_data(src)
+        .ok_or_else(|| RepoError::NotFound(src.to_string()))?;
+    match repo.get_tag_data(dst) {
+        Some(existing) if existing == command => return Ok(()),
+        Some(_) if !force => return Err(RepoError::AlreadyExists(dst.to_string())),
+        _ => {}
+    }
+    journal.apply(
+        repo,
+        &[Change::Add {
+            tag: dst.to_string(),
+            command,
+        }],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    #[test]
+    fn test_copy_in() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("copy");
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        repo.add_tag_data("other".to_string(), "echo other".to_string())
+            .unwrap();
+        copy_in(&mut repo, &journal, "test", "copied", false).unwrap();
+        assert_eq!(repo.get_tag_data("test").unwrap(), "echo test");
+        assert_eq!(repo.get_tag_data("copied").unwrap(), "echo test");
+        assert!(matches!(
+            copy_in(&mut repo, &journal, "test", "other", false),
+            Err(RepoError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            copy_in(&mut repo, &journal, "missing", "new", false),
+            Err(RepoError::NotFound(_))
+        ));
+        assert!(matches!(
+            copy_in(&mut repo, &journal, "test", "all", true),
+            Err(RepoError::Reserved(_))
+        ));
+        copy_in(&mut repo, &journal, "test", "other", true).unwrap();
+        assert_eq!(repo.get_tag_data("other").unwrap(), "echo test");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
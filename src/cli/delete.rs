@@ -0,0 +1,52 @@
+use crate::repo::journal::{Change, Journal};
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+
+/// Removes `tag`. Returns the layer that still defines `tag`,
+/// when a layered store has it in a layer other than the one written to.
+pub fn delete<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    tag: &str,
+) -> Result<Option<String>, RepoError> {
+    journal.apply(
+        repo,
+        &[Change::Delete {
+            tag: tag.to_string(),
+        }],
+    )?;
+    Ok(repo.get_origin(tag))
+}
+
+/// Removes every one of `tags` as one change, undone together.
+pub fn delete_all<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    tags: &[String],
+) -> Result<(), RepoError> {
+    let changes: Vec<Change> = tags
+        .iter()
+        .map(|tag| Change::Delete { tag: tag.clone() })
+        .collect();
+    journal.apply(repo, &changes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    #[test]
+    fn test_delete() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("delete");
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        assert_eq!(delete(&mut repo, &journal, "test").unwrap(), None);
+        assert_eq!(repo.get_tag_data("test"), None);
+        assert!(matches!(
+            delete(&mut repo, &journal, "test"),
+            Err(RepoError::NotFound(_))
+        ));
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
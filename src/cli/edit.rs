@@ -0,0 +1,119 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::Command;
+
+/// Separates the header of the file `edit_command` opens from the command.
+pub const CUT_LINE: &str = "# ------------------------ >8 ------------------------";
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// `$VISUAL`, else `$EDITOR`, else a platform default; a program followed by its arguments.
+pub fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|key| env::var(key).ok())
+        .find(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// `text` as a command: without the empty lines around it, like the newline
+/// editors and pipes add at the end, `None` when there is nothing but whitespace.
+pub fn command_from(text: &str) -> Option<String> {
+    let command = text
+        .trim_start_matches(['\n', '\r'])
+        .trim_end_matches(['\n', '\r']);
+    if command.trim().is_empty() {
+        return None;
+    }
+    Some(command.to_string())
+}
+
+/// The file `edit_command` opens: `tag` and where it comes from, then `command`.
+pub fn template(tag: &str, origin: Option<&str>, command: &str) -> String {
+    let mut text = format!("# tag: {}\n", tag);
+    if let Some(origin) = origin {
+        text.push_str(&format!("# layer: {}\n", origin));
+    }
+    text.push_str("# Write the command below the line, it can span several lines.\n");
+    text.push_str("# Everything above the line is ignored, an empty command cancels.\n");
+    text.push_str(CUT_LINE);
+    text.push('\n');
+    if !command.is_empty() {
+        text.push_str(command);
+        text.push('\n');
+    }
+    text
+}
+
+/// The command in a file written from `template`.
+pub fn parse(text: &str) -> Result<Option<String>> {
+    let Some((_, command)) = text.split_once(&format!("{}\n", CUT_LINE)) else {
+        bail!("the line \"{}\" was removed, nothing saved", CUT_LINE);
+    };
+    Ok(command_from(command))
+}
+
+/// Opens `editor()` on `command`, returns what was saved, `None` when it was emptied.
+pub fn edit_command(tag: &str, origin: Option<&str>, command: &str) -> Result<Option<String>> {
+    // created exclusively under a random name, readable by the user only.
+    let mut file = tempfile::Builder::new()
+        .prefix(&format!(
+            "tagcm-{}-",
+            tag.replace(super::tag::NAMESPACE_SEPARATOR, "_")
+        ))
+        .suffix(".sh")
+        .tempfile()
+        .context("failed to create a temporary file")?;
+    file.write_all(template(tag, origin, command).as_bytes())
+        .with_context(|| format!("failed to write {}", file.path().display()))?;
+    // closed so the editor may replace it, removed when dropped.
+    let path = file.into_temp_path();
+
+    let editor = editor();
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or_else(|| anyhow!("editor is empty"))?;
+    let status = Command::new(program).args(words).arg(&path).status();
+    let text = fs::read_to_string(&path);
+    drop(path);
+
+    let status = status.with_context(|| format!("failed to start the editor {}", editor))?;
+    if !status.success() {
+        bail!(
+            "the editor {} exited with {}, nothing saved",
+            editor,
+            status
+        );
+    }
+    parse(&text?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_round_trip() {
+        let command = "#!/bin/sh\nset -e\nmake build";
+        let text = template("k8s/build", Some("team"), command);
+        assert!(text.starts_with("# tag: k8s/build\n# layer: team\n"));
+        assert_eq!(parse(&text).unwrap().unwrap(), command);
+
+        let emptied = text.replace(command, "\n  \n");
+        assert_eq!(parse(&emptied).unwrap(), None);
+        assert!(parse("make build\n").is_err());
+    }
+
+    #[test]
+    fn test_command_from() {
+        assert_eq!(command_from("echo 1\necho 2\n").unwrap(), "echo 1\necho 2");
+        assert_eq!(command_from("echo\r\n").unwrap(), "echo");
+        assert_eq!(command_from("\n\necho\n").unwrap(), "echo");
+        assert_eq!(command_from("  echo  ").unwrap(), "  echo  ");
+        assert_eq!(command_from(" \n\n"), None);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::repo::cipher::Cipher;
+use crate::repo::encrypted_repository::{self, EncryptedRepository};
+use crate::repo::hashmap_repository::{self, HashMapRepository};
+use crate::repo::journal::Journal;
+use crate::repo::replace_file;
+use crate::repo::tag_data_repository::TagDataRepository;
+use anyhow::{bail, Result};
+
+/// Encrypts the plain store at `path`, and the journal next to it, with `passphrase`.
+pub fn encrypt(path: &str, passphrase: &str) -> Result<()> {
+    if encrypted_repository::is_encrypted(path) {
+        bail!("{} is already encrypted", path);
+    }
+    let mut plain = HashMapRepository::new();
+    plain.init(path)?;
+    let cipher = Cipher::new(passphrase);
+    let data = plain.get_all_data().into_iter().collect();
+    // the journal first: a store that is not converted yet can still be undone.
+    let journal = Journal::for_store(path).rewrite(Some(cipher.clone()))?;
+    if let Err(e) = EncryptedRepository::create(path, data, cipher) {
+        journal.rewrite(None)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Writes the encrypted store at `path`, and its journal, back in plain.
+pub fn decrypt(path: &str, passphrase: &str) -> Result<()> {
+    if !encrypted_repository::is_encrypted(path) {
+        bail!("{} is not encrypted", path);
+    }
+    let mut repo = EncryptedRepository::with_passphrase(passphrase);
+    repo.init(path)?;
+    let cipher = repo.cipher();
+    let journal = Journal::for_store(path)
+        .with_cipher(cipher.clone())
+        .rewrite(None)?;
+    if let Err(e) = replace_file(path, &hashmap_repository::plain_text(&repo.into_data())) {
+        journal.rewrite(cipher)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let path = std::env::temp_dir()
+            .join(format!("tagcm_encrypt_{}.json", std::process::id()))
+            .display()
+            .to_string();
+        fs::write(&path, r#"{"login": "TOKEN=s3cr3t ./login"}"#).unwrap();
+        let journal = Journal::for_store(&path);
+        let mut plain = HashMapRepository::new();
+        plain.init(&path).unwrap();
+        journal
+            .apply(
+                &mut plain,
+                &[crate::repo::journal::Change::Add {
+                    tag: "logout".to_string(),
+                    command: "TOKEN=s3cr3t ./logout".to_string(),
+                }],
+            )
+            .unwrap();
+
+        encrypt(&path, "p").unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("s3cr3t"));
+        assert!(!fs::read_to_string(journal.path())
+            .unwrap()
+            .contains("s3cr3t"));
+        assert!(encrypt(&path, "p").is_err());
+        assert!(decrypt(&path, "wrong").is_err());
+
+        decrypt(&path, "p").unwrap();
+        let mut repo = HashMapRepository::new();
+        repo.init(&path).unwrap();
+        assert_eq!(repo.get_tag_data("login").unwrap(), "TOKEN=s3cr3t ./login");
+        assert_eq!(journal.entries().unwrap().len(), 1);
+        assert!(decrypt(&path, "p").is_err());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use super::log::format_time;
+use super::secret::SecretRules;
+use super::OutputFormat;
+use crate::repo::journal::{Journal, Operation};
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Revision {
+    id: u64,
+    time: u64,
+    undone: bool,
+    #[serde(flatten)]
+    op: Operation,
+}
+
+// the operations that touched `tag`, newest first, following it back through renames.
+fn revisions(journal: &Journal, tag: &str) -> Result<Vec<Revision>, RepoError> {
+    let undone = journal.undone()?;
+    let mut name = tag.to_string();
+    let mut revisions = Vec::new();
+    for entry in journal.entries()?.into_iter().rev() {
+        for op in entry.ops().iter().rev() {
+            let touched = match op {
+                Operation::Add { tag, .. }
+                | Operation::Replace { tag, .. }
+                | Operation::Delete { tag, .. } => *tag == name,
+                Operation::Rename { tag, new_tag, .. } if *new_tag == name => {
+                    name = tag.clone();
+                    true
+                }
+                Operation::Rename { tag, .. } => *tag == name,
+            };
+            if touched {
+                revisions.push(Revision {
+                    id: entry.id,
+                    time: entry.time,
+                    undone: undone.contains(&entry.id),
+                    op: op.clone(),
+                });
+            }
+        }
+    }
+    Ok(revisions)
+}
+
+/// The recorded changes of `tag`, with the commands it replaced, newest first. The secrets
+/// are masked with `secrets` when given.
+pub fn history<T: TagDataRepository + ?Sized>(
+    repo: &T,
+    journal: &Journal,
+    tag: &str,
+    format: OutputFormat,
+    secrets: Option<&SecretRules>,
+) -> Result<String> {
+    let mut revisions = revisions(journal, tag)?;
+    if let Some(secrets) = secrets {
+        for revision in &mut revisions {
+            secrets.mask_op(&mut revision.op);
+        }
+    }
+    if revisions.is_empty() && repo.get_tag_data(tag).is_none() {
+        return Err(RepoError::NotFound(tag.to_string()).into());
+    }
+    if format == OutputFormat::Json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&revisions)?));
+    }
+    Ok(revisions
+        .iter()
+        .map(|revision| {
+            let undone = if revision.undone { " (undone)" } else { "" };
+            format!(
+                "#{} {}{}  {}\n",
+                revision.id,
+                format_time(revision.time),
+                undone,
+                revision.op
+            )
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    use crate::repo::journal::Change;
+
+    fn add(tag: &str, command: &str) -> Change {
+        Change::Add {
+            tag: tag.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("history");
+        journal.apply(&mut repo, &[add("old", "echo 1")]).unwrap();
+        journal
+            .apply(&mut repo, &[add("other", "echo other")])
+            .unwrap();
+        journal
+            .apply(
+                &mut repo,
+                &[Change::Rename {
+                    tag: "old".to_string(),
+                    new_tag: "new".to_string(),
+                }],
+            )
+            .unwrap();
+        journal.apply(&mut repo, &[add("new", "echo 2")]).unwrap();
+        journal.undo(&mut repo).unwrap();
+
+        let ops: Vec<(u64, bool, String)> = revisions(&journal, "new")
+            .unwrap()
+            .into_iter()
+            .map(|revision| (revision.id, revision.undone, revision.op.to_string()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (4, true, "replace new: echo 2 (was: echo 1)".to_string()),
+                (3, false, "rename old to new".to_string()),
+                (1, false, "add old: echo 1".to_string()),
+            ]
+        );
+
+        let text = history(&repo, &journal, "new", OutputFormat::Text, None).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.starts_with("#4 ") && text.contains(" (undone)  replace new"));
+        assert!(history(&repo, &journal, "missing", OutputFormat::Text, None).is_err());
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_history_masks_secrets() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("history_secrets");
+        journal
+            .apply(&mut repo, &[add("deploy", "TOKEN=abc make deploy")])
+            .unwrap();
+        journal
+            .apply(
+                &mut repo,
+                &[Change::Delete {
+                    tag: "deploy".to_string(),
+                }],
+            )
+            .unwrap();
+        let secrets = SecretRules::default();
+        let text = history(
+            &repo,
+            &journal,
+            "deploy",
+            OutputFormat::Text,
+            Some(&secrets),
+        )
+        .unwrap();
+        assert!(text.contains("delete deploy (was: TOKEN=******** make deploy)"));
+        assert!(text.contains("add deploy: TOKEN=******** make deploy"));
+        assert!(!text.contains("abc"));
+        let json = history(
+            &repo,
+            &journal,
+            "deploy",
+            OutputFormat::Json,
+            Some(&secrets),
+        )
+        .unwrap();
+        assert!(!json.contains("abc"));
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use super::secret::SecretRules;
+use super::OutputFormat;
+use crate::repo::journal::{Action, Entry, Journal};
+use anyhow::Result;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct LogEntry {
+    #[serde(flatten)]
+    entry: Entry,
+    /// The change was undone and not redone.
+    undone: bool,
+}
+
+/// `secs` since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`.
+pub(super) fn format_time(secs: u64) -> String {
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+    // civil_from_days, http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+/// The journal of a store, newest entry first, the secrets masked with `secrets` when given.
+pub fn log(
+    journal: &Journal,
+    format: OutputFormat,
+    secrets: Option<&SecretRules>,
+) -> Result<String> {
+    let undone = journal.undone()?;
+    let entries: Vec<LogEntry> = journal
+        .entries()?
+        .into_iter()
+        .rev()
+        .map(|mut entry| {
+            if let (Some(secrets), Action::Apply { ops }) = (secrets, &mut entry.action) {
+                ops.iter_mut().for_each(|op| secrets.mask_op(op));
+            }
+            LogEntry {
+                undone: undone.contains(&entry.id),
+                entry,
+            }
+        })
+        .collect();
+    if format == OutputFormat::Json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&entries)?));
+    }
+
+    let mut text = String::new();
+    for LogEntry { entry, undone } in entries {
+        text.push_str(&format!("#{} {}", entry.id, format_time(entry.time)));
+        if undone {
+            text.push_str(" (undone)");
+        }
+        text.push('\n');
+        match &entry.action {
+            Action::Apply { ops } => {
+                for op in ops {
+                    text.push_str(&format!("    {}\n", op));
+                }
+            }
+            Action::Undo { target } => text.push_str(&format!("    undo #{}\n", target)),
+            Action::Redo { target } => text.push_str(&format!("    redo #{}\n", target)),
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    use crate::repo::journal::Change;
+    use crate::repo::tag_data_repository::TagDataRepository;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_time(1792454399), "2026-10-19 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_log() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("log");
+        assert_eq!(log(&journal, OutputFormat::Text, None).unwrap(), "");
+        journal
+            .apply(
+                &mut repo,
+                &[Change::Add {
+                    tag: "test".to_string(),
+                    command: "echo test".to_string(),
+                }],
+            )
+            .unwrap();
+        journal.undo(&mut repo).unwrap();
+
+        let text = log(&journal, OutputFormat::Text, None).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("#2 "));
+        assert_eq!(lines[1], "    undo #1");
+        assert!(lines[2].starts_with("#1 ") && lines[2].ends_with(" (undone)"));
+        assert_eq!(lines[3], "    add test: echo test");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&log(&journal, OutputFormat::Json, None).unwrap()).unwrap();
+        assert_eq!(json[0]["action"], "undo");
+        assert_eq!(json[1]["undone"], true);
+        assert_eq!(json[1]["ops"][0]["op"], "add");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_log_masks_secrets() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("log_secrets");
+        journal
+            .apply(
+                &mut repo,
+                &[Change::Add {
+                    tag: "deploy".to_string(),
+                    command: "TOKEN=abc make deploy".to_string(),
+                }],
+            )
+            .unwrap();
+        let secrets = SecretRules::default();
+        let text = log(&journal, OutputFormat::Text, Some(&secrets)).unwrap();
+        assert_eq!(
+            text.lines().nth(1),
+            Some("    add deploy: TOKEN=******** make deploy")
+        );
+        let json = log(&journal, OutputFormat::Json, Some(&secrets)).unwrap();
+        assert!(json.contains("TOKEN=******** make deploy") && !json.contains("abc"));
+        let text = log(&journal, OutputFormat::Text, None).unwrap();
+        assert!(text.contains("TOKEN=abc make deploy"));
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,484 @@
+use super::search::{help_line, CrosstermEvents, EventSource, TerminalGuard};
+use crate::repo::cipher::Cipher;
+use crate::repo::encrypted_repository;
+use crate::repo::hashmap_repository;
+use crate::repo::passphrase;
+use anyhow::{bail, Context, Result};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, stdout, IsTerminal};
+
+/// One of the two versions being merged.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+/// A tag both sides changed since the base, each differently. `None` is a removed tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub tag: String,
+    pub base: Option<String>,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+impl Conflict {
+    pub fn side(&self, side: Side) -> Option<&String> {
+        match side {
+            Side::Ours => self.ours.as_ref(),
+            Side::Theirs => self.theirs.as_ref(),
+        }
+    }
+
+    /// Puts the version of `side` into `merged`.
+    pub fn resolve(&self, merged: &mut HashMap<String, String>, side: Side) {
+        match self.side(side) {
+            Some(command) => merged.insert(self.tag.clone(), command.clone()),
+            None => merged.remove(&self.tag),
+        };
+    }
+}
+
+/// The tags of both sides since `base`: a tag changed on one side takes that change,
+/// also when it was removed. Tags changed differently on both sides are left out of
+/// the merged tags and returned as conflicts, in the order of their tags.
+pub fn merge(
+    base: &HashMap<String, String>,
+    ours: &HashMap<String, String>,
+    theirs: &HashMap<String, String>,
+) -> (HashMap<String, String>, Vec<Conflict>) {
+    let tags: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    let mut merged = HashMap::new();
+    let mut conflicts = Vec::new();
+    for tag in tags {
+        let (base, ours, theirs) = (base.get(tag), ours.get(tag), theirs.get(tag));
+        let command = if ours == theirs || theirs == base {
+            ours
+        } else if ours == base {
+            theirs
+        } else {
+            conflicts.push(Conflict {
+                tag: tag.clone(),
+                base: base.cloned(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            continue;
+        };
+        if let Some(command) = command {
+            merged.insert(tag.clone(), command.clone());
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Reads stores, plain or encrypted, asking for the passphrase of encrypted ones at most once.
+#[derive(Default)]
+pub struct StoreParser {
+    passphrase: Option<String>,
+}
+
+impl StoreParser {
+    /// The tags of the store `text` read from `path` and the key of an encrypted one.
+    /// Empty text has no tags, git gives it for a file one side does not have.
+    pub fn parse(
+        &mut self,
+        text: &str,
+        path: &str,
+    ) -> Result<(HashMap<String, String>, Option<Cipher>)> {
+        if text.trim().is_empty() {
+            return Ok((HashMap::new(), None));
+        }
+        // an encrypted store is an object with more than strings in it.
+        if let Ok(data) = serde_json::from_str(text) {
+            return Ok((data, None));
+        }
+        let cached = &mut self.passphrase;
+        let (data, cipher) = encrypted_repository::decrypt_text(text, path, || {
+            if cached.is_none() {
+                *cached = Some(passphrase::get(path)?);
+            }
+            Ok(cached.clone().unwrap_or_default())
+        })?;
+        Ok((data, Some(cipher)))
+    }
+}
+
+/// `data` as the text of a store, encrypted with `cipher` when there is one.
+pub fn store_text(data: &HashMap<String, String>, cipher: Option<&Cipher>) -> String {
+    match cipher {
+        Some(cipher) => encrypted_repository::encrypt_text(data, cipher),
+        None => hashmap_repository::plain_text(data),
+    }
+}
+
+pub struct MergeOptions {
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+    /// Where the result goes, `ours` like git expects of a merge driver.
+    pub output: String,
+    /// The side conflicts take without asking.
+    pub prefer: Option<Side>,
+}
+
+/// Merges the stores of `options` tag by tag into the output, encrypted when ours is.
+/// Conflicts take the preferred side, or are asked about on a terminal. Those left
+/// keep our version in the output and are returned.
+pub fn merge_files(options: &MergeOptions) -> Result<Vec<Conflict>> {
+    let mut parser = StoreParser::default();
+    let mut read = |path: &str| {
+        let text = fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?;
+        parser.parse(&text, path)
+    };
+    let (base, base_cipher) = read(&options.base)?;
+    let (ours, ours_cipher) = read(&options.ours)?;
+    let (theirs, theirs_cipher) = read(&options.theirs)?;
+    let (mut merged, mut conflicts) = merge(&base, &ours, &theirs);
+
+    let choices = match options.prefer {
+        Some(side) => Some(vec![side; conflicts.len()]),
+        None if !conflicts.is_empty() && io::stdin().is_terminal() && stdout().is_terminal() => {
+            resolve_interactively(&options.output, &conflicts)?
+        }
+        None => None,
+    };
+    match choices {
+        Some(choices) => {
+            for (conflict, side) in conflicts.iter().zip(choices) {
+                conflict.resolve(&mut merged, side);
+            }
+            conflicts.clear();
+        }
+        None => {
+            for conflict in &conflicts {
+                conflict.resolve(&mut merged, Side::Ours);
+            }
+        }
+    }
+
+    let cipher = ours_cipher.or(theirs_cipher).or(base_cipher);
+    fs::write(&options.output, store_text(&merged, cipher.as_ref()))
+        .with_context(|| format!("failed to write {}", options.output))?;
+    Ok(conflicts)
+}
+
+/// The side to take for each of `conflicts`, None when the user gives up.
+fn resolve_interactively(path: &str, conflicts: &[Conflict]) -> Result<Option<Vec<Side>>> {
+    let _guard = TerminalGuard::new(false)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut events = CrosstermEvents::new()?;
+    Ok(run_resolver(
+        &mut terminal,
+        Resolver::new(path, conflicts),
+        &mut events,
+    )?)
+}
+
+/// State of the conflict resolver: a choice per conflict and the one selected.
+struct Resolver<'a> {
+    path: &'a str,
+    conflicts: &'a [Conflict],
+    choices: Vec<Option<Side>>,
+    selected: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(path: &'a str, conflicts: &'a [Conflict]) -> Self {
+        Resolver {
+            path,
+            conflicts,
+            choices: vec![None; conflicts.len()],
+            selected: 0,
+        }
+    }
+
+    fn choose(&mut self, side: Side) {
+        self.choices[self.selected] = Some(side);
+        self.selected = (self.selected + 1).min(self.conflicts.len() - 1);
+    }
+
+    /// Every choice once all are made, otherwise selects the first one left.
+    fn finish(&mut self) -> Option<Vec<Side>> {
+        match self.choices.iter().position(Option::is_none) {
+            Some(open) => {
+                self.selected = open;
+                None
+            }
+            None => Some(self.choices.iter().flatten().copied().collect()),
+        }
+    }
+}
+
+fn run_resolver<B: Backend, E: EventSource>(
+    terminal: &mut Terminal<B>,
+    mut resolver: Resolver,
+    events: &mut E,
+) -> io::Result<Option<Vec<Side>>> {
+    loop {
+        terminal.draw(|f| render(f, &resolver))?;
+
+        let Some(event) = events.next_event()? else {
+            return Ok(None);
+        };
+        if let Event::Resize(_, _) = event {
+            terminal.autoresize()?;
+            continue;
+        }
+        let Event::Key(key) = event else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+            KeyCode::Up | KeyCode::Char('k') => {
+                resolver.selected = resolver.selected.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                resolver.selected = (resolver.selected + 1).min(resolver.conflicts.len() - 1);
+            }
+            KeyCode::Left | KeyCode::Char('o') => resolver.choose(Side::Ours),
+            KeyCode::Right | KeyCode::Char('t') => resolver.choose(Side::Theirs),
+            KeyCode::Enter => {
+                if let Some(choices) = resolver.finish() {
+                    return Ok(Some(choices));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+const TITLE_CONFLICTS: &str = "Conflicting tags";
+const REMOVED: &str = "(removed)";
+
+fn version(command: Option<&String>) -> &str {
+    command.map_or(REMOVED, String::as_str)
+}
+
+fn render(f: &mut Frame, resolver: &Resolver) {
+    let help = vec![
+        help_line("↑/↓".to_string(), " select a tag"),
+        help_line("←/o, →/t".to_string(), " take ours or theirs"),
+        help_line(
+            "Enter".to_string(),
+            " write the merge once every tag is chosen",
+        ),
+        help_line("Esc".to_string(), " give up, keeping ours"),
+    ];
+    let [help_area, list_area, versions_area] = Layout::vertical([
+        Constraint::Length(help.len() as u16),
+        Constraint::Min(3),
+        Constraint::Length(8),
+    ])
+    .areas(f.size());
+    f.render_widget(Paragraph::new(help), help_area);
+
+    let items: Vec<ListItem> = resolver
+        .conflicts
+        .iter()
+        .zip(&resolver.choices)
+        .enumerate()
+        .map(|(i, (conflict, choice))| {
+            let choice = match choice {
+                Some(Side::Ours) => "ours  ",
+                Some(Side::Theirs) => "theirs",
+                None => "?     ",
+            };
+            let mut span = Span::raw(format!("[{}] {}", choice, conflict.tag));
+            if i == resolver.selected {
+                span = span.reversed();
+            }
+            ListItem::new(Line::from(span))
+        })
+        .collect();
+    let decided = resolver.choices.iter().flatten().count();
+    let title = format!(
+        "{} in {} ({}/{} chosen)",
+        TITLE_CONFLICTS,
+        resolver.path,
+        decided,
+        resolver.conflicts.len()
+    );
+    f.render_widget(
+        List::new(items).block(Block::default().borders(Borders::ALL).title(title)),
+        list_area,
+    );
+
+    let conflict = &resolver.conflicts[resolver.selected];
+    let columns = Layout::horizontal([Constraint::Ratio(1, 3); 3]).split(versions_area);
+    let versions = [
+        ("Base", version(conflict.base.as_ref())),
+        ("Ours", version(conflict.ours.as_ref())),
+        ("Theirs", version(conflict.theirs.as_ref())),
+    ];
+    for ((title, command), area) in versions.into_iter().zip(columns.iter()) {
+        let paragraph = Paragraph::new(command)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title(title));
+        f.render_widget(paragraph, *area);
+    }
+}
+
+/// Fails when `conflicts` are left, naming them.
+pub fn check(output: &str, conflicts: &[Conflict]) -> Result<()> {
+    if !conflicts.is_empty() {
+        let tags: Vec<&str> = conflicts.iter().map(|c| c.tag.as_str()).collect();
+        bail!(
+            "tags changed differently on both sides: {}, {} keeps ours, choose with --prefer ours or --prefer theirs or on a terminal",
+            tags.join(", "),
+            output
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::cipher::KdfParams;
+    use crossterm::event::KeyEvent;
+    use std::collections::VecDeque;
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(tag, command)| (tag.to_string(), command.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_different_tags() {
+        let base = map(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let ours = map(&[("a", "1 ours"), ("b", "2"), ("d", "4")]);
+        let theirs = map(&[("a", "1"), ("c", "3"), ("e", "5")]);
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+        assert_eq!(merged, map(&[("a", "1 ours"), ("d", "4"), ("e", "5")]));
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_merge_same_change_is_no_conflict() {
+        let base = map(&[("a", "1")]);
+        let both = map(&[("a", "2"), ("b", "3")]);
+        assert_eq!(merge(&base, &both, &both), (both.clone(), Vec::new()));
+    }
+
+    #[test]
+    fn test_merge_conflicts() {
+        let base = map(&[("a", "1"), ("b", "2")]);
+        let ours = map(&[("a", "ours"), ("c", "ours")]);
+        let theirs = map(&[("a", "theirs"), ("b", "theirs"), ("c", "theirs")]);
+        let (mut merged, conflicts) = merge(&base, &ours, &theirs);
+        assert!(merged.is_empty());
+        let tags: Vec<&str> = conflicts.iter().map(|c| c.tag.as_str()).collect();
+        // b was removed here and changed there.
+        assert_eq!(tags, ["a", "b", "c"]);
+        assert_eq!(conflicts[1].ours, None);
+
+        for conflict in &conflicts {
+            conflict.resolve(&mut merged, Side::Theirs);
+        }
+        assert_eq!(merged, theirs);
+        conflicts[1].resolve(&mut merged, Side::Ours);
+        assert!(!merged.contains_key("b"));
+    }
+
+    #[test]
+    fn test_parse_stores() {
+        let mut parser = StoreParser::default();
+        assert!(parser.parse("\n", "base").unwrap().0.is_empty());
+        let (data, cipher) = parser.parse(r#"{"ls": "ls -la"}"#, "ours").unwrap();
+        assert_eq!((data, cipher.is_none()), (map(&[("ls", "ls -la")]), true));
+        assert!(parser.parse("[1, 2]", "theirs").is_err());
+
+        // fast enough for tests, far too weak for anything else.
+        let params = KdfParams {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        let cipher = Cipher::derive("p", b"0123456789abcdef", params).unwrap();
+        let text = store_text(&map(&[("db", "psql")]), Some(&cipher));
+        let mut parser = StoreParser {
+            passphrase: Some("p".to_string()),
+        };
+        let (data, cipher) = parser.parse(&text, "encrypted").unwrap();
+        assert_eq!((data, cipher.is_some()), (map(&[("db", "psql")]), true));
+    }
+
+    struct ScriptedEvents(VecDeque<Event>);
+
+    impl EventSource for ScriptedEvents {
+        fn next_event(&mut self) -> io::Result<Option<Event>> {
+            Ok(self.0.pop_front())
+        }
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn conflicts() -> Vec<Conflict> {
+        ["a", "b"]
+            .map(|tag| Conflict {
+                tag: tag.to_string(),
+                base: Some("base".to_string()),
+                ours: Some(format!("{} ours", tag)),
+                theirs: None,
+            })
+            .to_vec()
+    }
+
+    fn run_scripted(
+        conflicts: &[Conflict],
+        events: Vec<Event>,
+    ) -> (Option<Vec<Side>>, Terminal<backend::TestBackend>) {
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 20)).unwrap();
+        let resolver = Resolver::new("tags.json", conflicts);
+        let choices =
+            run_resolver(&mut terminal, resolver, &mut ScriptedEvents(events.into())).unwrap();
+        (choices, terminal)
+    }
+
+    fn buffer_text(terminal: &Terminal<backend::TestBackend>) -> String {
+        let buffer = terminal.backend().buffer();
+        buffer.content.iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn test_resolver_chooses() {
+        let conflicts = conflicts();
+        // enter with a tag left selects it instead of finishing.
+        let events = vec![key(KeyCode::Char('t')), key(KeyCode::Enter)];
+        let (choices, terminal) = run_scripted(&conflicts, events.clone());
+        assert_eq!(choices, None);
+        let text = buffer_text(&terminal);
+        assert!(text.contains("[theirs] a"));
+        assert!(text.contains("(1/2 chosen)"));
+        assert!(text.contains("b ours") && text.contains("(removed)"));
+
+        let mut events = events;
+        events.extend([key(KeyCode::Char('o')), key(KeyCode::Enter)]);
+        let (choices, _) = run_scripted(&conflicts, events);
+        assert_eq!(choices, Some(vec![Side::Theirs, Side::Ours]));
+    }
+
+    #[test]
+    fn test_resolver_gives_up() {
+        let conflicts = conflicts();
+        let (choices, _) = run_scripted(&conflicts, vec![key(KeyCode::Esc)]);
+        assert_eq!(choices, None);
+        let (choices, _) = run_scripted(&conflicts, vec![key(KeyCode::Char('o'))]);
+        assert_eq!(choices, None);
+    }
+}
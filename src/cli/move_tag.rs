@@ -0,0 +1,112 @@
+use crate::repo::journal::Change;
+use crate::repo::layered_repository::LayeredRepository;
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::{bail, Result};
+
+/// Moves `tag` from the layer it comes from to the layer `to`, where it must not
+/// exist yet unless `force`. Returns the layer it was moved from.
+pub fn move_tag(repo: &mut LayeredRepository, tag: &str, to: &str, force: bool) -> Result<String> {
+    let from = repo
+        .layer_of(tag)
+        .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+    if from == to {
+        bail!("tag {} is already in the {} layer", tag, to);
+    }
+    // each half goes to the journal of its layer, so undo works on either.
+    let (from_journal, to_journal) = (repo.journal(&from)?, repo.journal(to)?);
+    let command = repo.get_tag_data(tag).unwrap();
+
+    let target = repo.layer_mut(to)?;
+    match target.get_tag_data(tag) {
+        Some(existing) if existing != command && !force => {
+            return Err(RepoError::AlreadyExists(tag.to_string()).into());
+        }
+        _ => {}
+    }
+    to_journal.apply(
+        target,
+        &[Change::Add {
+            tag: tag.to_string(),
+            command,
+        }],
+    )?;
+    let removed = from_journal.apply(
+        repo.layer_mut(&from)?,
+        &[Change::Delete {
+            tag: tag.to_string(),
+        }],
+    );
+    if let Err(e) = removed {
+        to_journal.undo(repo.layer_mut(to)?)?;
+        return Err(e.into());
+    }
+    Ok(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::hashmap_repository::HashMapRepository;
+    use crate::repo::journal::Journal;
+    use crate::repo::layered_repository::{GLOBAL_LAYER, PROJECT_LAYER};
+
+    fn store(
+        dir: &std::path::Path,
+        name: &str,
+        entries: &[(&str, &str)],
+    ) -> Box<HashMapRepository> {
+        let mut repo = HashMapRepository::new();
+        repo.init(&dir.join(name).display().to_string()).unwrap();
+        for (tag, command) in entries {
+            repo.add_tag_data(tag.to_string(), command.to_string())
+                .unwrap();
+        }
+        Box::new(repo)
+    }
+
+    #[test]
+    fn test_move_tag() {
+        let dir = std::env::temp_dir().join(format!("tagcm_move_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut repo = LayeredRepository::new();
+        repo.add_layer(
+            PROJECT_LAYER,
+            store(
+                &dir,
+                "project.json",
+                &[("build", "cargo build"), ("ls", "ls")],
+            ),
+        );
+        repo.add_layer(
+            GLOBAL_LAYER,
+            store(&dir, "global.json", &[("ls", "ls -la")]),
+        );
+
+        assert_eq!(
+            move_tag(&mut repo, "build", GLOBAL_LAYER, false).unwrap(),
+            PROJECT_LAYER
+        );
+        assert_eq!(repo.layer_of("build").unwrap(), GLOBAL_LAYER);
+        assert!(move_tag(&mut repo, "build", GLOBAL_LAYER, false).is_err());
+        assert!(move_tag(&mut repo, "build", "nothing", false).is_err());
+        assert!(move_tag(&mut repo, "missing", PROJECT_LAYER, false).is_err());
+
+        let collision = move_tag(&mut repo, "ls", GLOBAL_LAYER, false).unwrap_err();
+        assert!(matches!(
+            collision.downcast_ref::<RepoError>(),
+            Some(RepoError::AlreadyExists(_))
+        ));
+        move_tag(&mut repo, "ls", GLOBAL_LAYER, true).unwrap();
+        assert_eq!(repo.get_tag_data("ls").unwrap(), "ls");
+        assert_eq!(repo.layer_of("ls").unwrap(), GLOBAL_LAYER);
+
+        // both halves are recorded, each in the journal of its layer.
+        let project = Journal::for_store(&dir.join("project.json").display().to_string());
+        assert_eq!(project.entries().unwrap().len(), 2);
+        project
+            .undo(repo.layer_mut(PROJECT_LAYER).unwrap())
+            .unwrap();
+        assert_eq!(repo.layer_of("ls").unwrap(), PROJECT_LAYER);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::repo::journal::{Change, Journal};
+use crate::repo::layered_repository::LayeredRepository;
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::Result;
+
+use super::tag;
+
+/// Moves the command of `tag` to `new_tag` within the layer `tag` comes from, where
+/// `new_tag` must not exist yet unless `force`. Returns that layer.
+pub fn rename(
+    repo: &mut LayeredRepository,
+    tag: &str,
+    new_tag: &str,
+    force: bool,
+) -> Result<String> {
+    let layer = repo
+        .layer_of(tag)
+        .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+    let journal = repo.journal(&layer)?;
+    rename_in(repo.layer_mut(&layer)?, &journal, tag, new_tag, force)?;
+    Ok(layer)
+}
+
+fn rename_in<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    tag: &str,
+    new_tag: &str,
+    force: bool,
+) -> Result<(), RepoError> {
+    tag::validate(new_tag)?;
+    let command = repo
+        .get_tag_data(tag)
+        .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+    if tag == new_tag {
+        return Ok(());
+    }
+    let changes = match repo.get_tag_data(new_tag) {
+        // replacing keeps the overwritten command in the journal.
+        Some(_) if force => vec![
+            Change::Add {
+                tag: new_tag.to_string(),
+                command,
+            },
+            Change::Delete {
+                tag: tag.to_string(),
+            },
+        ],
+        _ => vec![Change::Rename {
+            tag: tag.to_string(),
+            new_tag: new_tag.to_string(),
+        }],
+    };
+    journal.apply(repo, &changes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    #[test]
+    fn test_rename_in() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("rename");
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        repo.add_tag_data("other".to_string(), "echo other".to_string())
+            .unwrap();
+        rename_in(&mut repo, &journal, "test", "renamed", false).unwrap();
+        assert_eq!(repo.get_tag_data("test"), None);
+        assert_eq!(repo.get_tag_data("renamed").unwrap(), "echo test");
+        assert!(matches!(
+            rename_in(&mut repo, &journal, "renamed", "other", false),
+            Err(RepoError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            rename_in(&mut repo, &journal, "missing", "new", false),
+            Err(RepoError::NotFound(_))
+        ));
+        assert!(matches!(
+            rename_in(&mut repo, &journal, "renamed", "all", false),
+            Err(RepoError::Reserved(_))
+        ));
+        rename_in(&mut repo, &journal, "renamed", "renamed", true).unwrap();
+        assert_eq!(repo.get_tag_data("renamed").unwrap(), "echo test");
+
+        rename_in(&mut repo, &journal, "renamed", "other", true).unwrap();
+        assert_eq!(repo.get_tag_data("renamed"), None);
+        assert_eq!(repo.get_tag_data("other").unwrap(), "echo test");
+        journal.undo(&mut repo).unwrap();
+        assert_eq!(repo.get_tag_data("other").unwrap(), "echo other");
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::{anyhow, Result};
+use std::process::{Command, ExitStatus};
+
+/// Runs the command of `tag` with `shell`, a program followed by its arguments such as `sh -c`.
+pub fn run<T: TagDataRepository>(repo: &T, tag: &str, shell: &str) -> Result<ExitStatus> {
+    let command = repo
+        .get_tag_data(tag)
+        .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+    let mut shell = shell.split_whitespace();
+    let program = shell.next().ok_or_else(|| anyhow!("shell is empty"))?;
+    Ok(Command::new(program).args(shell).arg(command).status()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::UnitTestRepository;
+    use crate::config::Config;
+
+    #[test]
+    fn test_run() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("ok".to_string(), "exit 0".to_string())
+            .unwrap();
+        repo.add_tag_data("fail".to_string(), "exit 3".to_string())
+            .unwrap();
+        let shell = Config::default().shell;
+
+        assert!(run(&repo, "ok", &shell).unwrap().success());
+        assert_eq!(run(&repo, "fail", &shell).unwrap().code(), Some(3));
+        assert!(run(&repo, "missing", &shell).is_err());
+        assert!(run(&repo, "ok", "").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use super::risk::RiskRules;
+use super::search::TagData;
+use super::secret::SecretRules;
+use super::tag;
+use super::OutputFormat;
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+use anyhow::Result;
+
+/// Renders `entries` one per line, or as a JSON array.
+pub fn format(entries: &[TagData], format: OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Text => entries.iter().map(|data| format!("{}\n", data)).collect(),
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(entries)?),
+    })
+}
+
+/// Every entry of `repo`, sorted by tag.
+pub fn show_all<T: TagDataRepository + ?Sized>(repo: &T) -> Vec<TagData> {
+    let mut entries: Vec<TagData> = repo
+        .get_all_data()
+        .into_iter()
+        .map(|(tag, command)| {
+            let origin = repo.get_origin(&tag);
+            TagData {
+                origin,
+                ..TagData::new(tag, command)
+            }
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// The entries in the subtree of `namespace`, e.g. `k8s/`, sorted by tag.
+pub fn show_namespace<T: TagDataRepository + ?Sized>(
+    repo: &T,
+    namespace: &str,
+) -> Result<Vec<TagData>, RepoError> {
+    let prefix = tag::namespace_prefix(namespace)?;
+    let entries: Vec<TagData> = show_all(repo)
+        .into_iter()
+        .filter(|data| data.tag.starts_with(&prefix))
+        .collect();
+    if entries.is_empty() {
+        return Err(RepoError::NotFound(prefix));
+    }
+    Ok(entries)
+}
+
+pub fn show<T: TagDataRepository + ?Sized>(repo: &T, tag: String) -> Result<TagData, RepoError> {
+    TagData::from_repo(repo, tag.clone()).ok_or(RepoError::NotFound(tag))
+}
+
+/// The entries `target` names: every one for `all`, those of a namespace for a target
+/// ending in `/`, else the single tag.
+pub fn show_target<T: TagDataRepository + ?Sized>(repo: &T, target: &str) -> Result<Vec<TagData>> {
+    Ok(if target == super::ALL_SUBCOMMAND {
+        show_all(repo)
+    } else if target.ends_with(tag::NAMESPACE_SEPARATOR) {
+        show_namespace(repo, target)?
+    } else {
+        vec![show(repo, target.to_string())?]
+    })
+}
+
+/// Marks the dangerous commands of `entries` and masks their secrets, unless `reveal`.
+pub fn annotate(entries: &mut [TagData], risk: &RiskRules, secrets: &SecretRules, reveal: bool) {
+    risk.mark(entries);
+    if !reveal {
+        secrets.mask(entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::unittest_repository::UnitTestRepository;
+    #[test]
+    fn test_show() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        let data = show(&repo, "test".to_string()).unwrap();
+        assert_eq!(data.command, "echo test");
+        assert!(matches!(
+            show(&repo, "test2".to_string()),
+            Err(RepoError::NotFound(_))
+        ));
+        assert_eq!(
+            format(std::slice::from_ref(&data), OutputFormat::Text).unwrap(),
+            "test: echo test\n"
+        );
+        assert!(format(&[data], OutputFormat::Json)
+            .unwrap()
+            .contains("\"command\": \"echo test\""));
+    }
+
+    #[test]
+    fn test_show_all() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("test2".to_string(), "echo test2".to_string())
+            .unwrap();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        repo.add_tag_data("k8s/logs".to_string(), "kubectl logs".to_string())
+            .unwrap();
+        repo.add_tag_data("k8sx".to_string(), "echo".to_string())
+            .unwrap();
+        assert_eq!(
+            show_namespace(&repo, "k8s/").unwrap(),
+            vec![TagData::new(
+                "k8s/logs".to_string(),
+                "kubectl logs".to_string()
+            )]
+        );
+        assert_eq!(show_namespace(&repo, "k8s").unwrap().len(), 1);
+        assert!(matches!(
+            show_namespace(&repo, "k8s/logs"),
+            Err(RepoError::NotFound(_))
+        ));
+        repo.remove_tag_data("k8s/logs").unwrap();
+        repo.remove_tag_data("k8sx").unwrap();
+
+        let entries = show_all(&repo);
+        assert_eq!(
+            entries,
+            vec![
+                TagData::new("test".to_string(), "echo test".to_string()),
+                TagData::new("test2".to_string(), "echo test2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_show_target() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("k8s/logs".to_string(), "kubectl logs".to_string())
+            .unwrap();
+        repo.add_tag_data("api".to_string(), "TOKEN=s3cr3t rm -rf x".to_string())
+            .unwrap();
+        assert_eq!(show_target(&repo, "all").unwrap().len(), 2);
+        assert_eq!(show_target(&repo, "k8s/").unwrap()[0].tag, "k8s/logs");
+        assert!(show_target(&repo, "missing").is_err());
+
+        let mut entries = show_target(&repo, "api").unwrap();
+        let (risk, secrets) = (RiskRules::default(), SecretRules::default());
+        annotate(&mut entries, &risk, &secrets, true);
+        assert_eq!(entries[0].command, "TOKEN=s3cr3t rm -rf x");
+        annotate(&mut entries, &risk, &secrets, false);
+        assert_eq!(entries[0].command, "TOKEN=******** rm -rf x");
+        assert_eq!(entries[0].risks, vec!["recursive rm"]);
+    }
+}
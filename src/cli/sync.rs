@@ -0,0 +1,438 @@
+use super::merge::{self, Side, StoreParser};
+use crate::config::SyncConfig;
+use crate::repo::cipher::Cipher;
+use crate::repo::journal::Journal;
+use crate::repo::layered_repository::LayeredRepository;
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Journals are the undo history of one machine, they stay out of the repository.
+const EXCLUDE: &str = "*.journal";
+
+/// Which side wins for a tag changed differently here and on the remote.
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum Prefer {
+    Local,
+    Remote,
+}
+
+pub struct SyncOptions {
+    /// The git working tree the stores are kept in.
+    pub dir: PathBuf,
+    pub remote: Option<String>,
+    pub branch: String,
+    pub prefer: Option<Prefer>,
+}
+
+/// What `sync` did, in commits.
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub received: usize,
+    pub sent: usize,
+    /// Tags changed differently on both sides, settled with `--prefer`.
+    pub conflicts: Vec<String>,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run git, is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn git_succeeds(dir: &Path, args: &[&str]) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn count(dir: &Path, range: &str) -> Result<usize> {
+    let count = git(dir, &["rev-list", "--count", range])?;
+    count
+        .trim()
+        .parse()
+        .with_context(|| format!("unexpected output of git rev-list: {}", count))
+}
+
+/// Whether `dir` is the top of a git working tree.
+pub fn is_repository(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+/// The stores among `paths` kept in `dir`, as paths relative to it with `/` between
+/// the parts like git names them. The stores do not need to exist yet.
+pub fn stores_in(dir: &Path, paths: &[String]) -> Vec<String> {
+    let Ok(dir) = dir.canonicalize() else {
+        return Vec::new();
+    };
+    paths
+        .iter()
+        .filter_map(|path| {
+            let path = Path::new(path);
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize().ok()?,
+                _ => std::env::current_dir().ok()?,
+            };
+            let relative = parent.strip_prefix(&dir).ok()?.join(path.file_name()?);
+            let parts: Vec<_> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            Some(parts.join("/"))
+        })
+        .collect()
+}
+
+/// Makes `dir` a git working tree on `branch`, unless it is one, and keeps the journals
+/// out of it.
+fn init(dir: &Path, branch: &str) -> Result<()> {
+    if !is_repository(dir) {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        git(dir, &["init", "--quiet"])?;
+        git(
+            dir,
+            &["symbolic-ref", "HEAD", &format!("refs/heads/{}", branch)],
+        )?;
+    }
+    let exclude = dir.join(".git").join("info").join("exclude");
+    let text = fs::read_to_string(&exclude).unwrap_or_default();
+    if !text.lines().any(|line| line == EXCLUDE) {
+        if let Some(parent) = exclude.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let separator = if text.is_empty() || text.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        fs::write(&exclude, format!("{}{}{}\n", text, separator, EXCLUDE))?;
+    }
+    Ok(())
+}
+
+/// Commits the changes to `stores` with `message`, false when there were none.
+fn commit(dir: &Path, stores: &[String], message: &str) -> Result<bool> {
+    let existing: Vec<&str> = stores
+        .iter()
+        .filter(|store| dir.join(store).exists())
+        .map(String::as_str)
+        .collect();
+    if existing.is_empty() {
+        return Ok(false);
+    }
+    git(dir, &[&["add", "--"], existing.as_slice()].concat())?;
+    if git_succeeds(
+        dir,
+        &[&["diff", "--cached", "--quiet", "--"], existing.as_slice()].concat(),
+    ) {
+        return Ok(false);
+    }
+    git(
+        dir,
+        &[
+            &["commit", "--quiet", "-m", message, "--"],
+            existing.as_slice(),
+        ]
+        .concat(),
+    )?;
+    Ok(true)
+}
+
+/// Reads stores from git, asking for the passphrase of encrypted ones at most once.
+struct Reader<'a> {
+    dir: &'a Path,
+    parser: StoreParser,
+}
+
+impl Reader<'_> {
+    /// The tags of `store` at `rev` and the key it is encrypted with, if it is. A store
+    /// that is not there has no tags.
+    fn read(
+        &mut self,
+        rev: &str,
+        store: &str,
+    ) -> Result<(HashMap<String, String>, Option<Cipher>)> {
+        // a revision that does not resolve is an error, not an empty store.
+        git(
+            self.dir,
+            &["rev-parse", "--verify", &format!("{}^{{commit}}", rev)],
+        )?;
+        let object = format!("{}:{}", rev, store);
+        if !git_succeeds(self.dir, &["cat-file", "-e", &object]) {
+            return Ok((HashMap::new(), None));
+        }
+        let text = git(self.dir, &["show", &object])?;
+        self.parser.parse(&text, store)
+    }
+
+    /// The text of `store` merged from `base` and the local and fetched commits, with the
+    /// tags changed on both sides. None when the store is on no side.
+    fn merge(
+        &mut self,
+        base: &str,
+        store: &str,
+        prefer: Option<Prefer>,
+    ) -> Result<(Option<String>, Vec<String>)> {
+        let (base, base_cipher) = self.read(base, store)?;
+        let (local, local_cipher) = self.read("HEAD", store)?;
+        let (remote, remote_cipher) = self.read("FETCH_HEAD", store)?;
+        let (mut merged, conflicts) = merge::merge(&base, &local, &remote);
+        let tags: Vec<String> = conflicts.iter().map(|c| c.tag.clone()).collect();
+        let side = match prefer {
+            Some(Prefer::Local) => Side::Ours,
+            Some(Prefer::Remote) => Side::Theirs,
+            None if tags.is_empty() => Side::Ours,
+            None => bail!(
+                "tags changed both here and on the remote: {}, choose a side with --prefer local or --prefer remote",
+                tags.join(", ")
+            ),
+        };
+        for conflict in &conflicts {
+            conflict.resolve(&mut merged, side);
+        }
+        let cipher = local_cipher.or(remote_cipher).or(base_cipher);
+        if cipher.is_none() && merged.is_empty() && !self.dir.join(store).exists() {
+            return Ok((None, tags));
+        }
+        Ok((Some(merge::store_text(&merged, cipher.as_ref())), tags))
+    }
+}
+
+/// Merges the fetched commit into the local branch, `stores` tag by tag and any other
+/// file the way git does. Leaves nothing behind when it gives up.
+fn merge_fetched(dir: &Path, stores: &[String], prefer: Option<Prefer>) -> Result<Vec<String>> {
+    let base = git(dir, &["merge-base", "HEAD", "FETCH_HEAD"])?;
+    // the stores may conflict as text, they are written below.
+    _ = git_succeeds(dir, &["merge", "--no-commit", "--no-ff", "FETCH_HEAD"]);
+    let abort = |error: anyhow::Error| {
+        _ = git_succeeds(dir, &["merge", "--abort"]);
+        error
+    };
+    let unmerged = git(dir, &["diff", "--name-only", "--diff-filter=U"]).map_err(abort)?;
+    if let Some(file) = unmerged
+        .lines()
+        .find(|file| !stores.iter().any(|s| s == file))
+    {
+        return Err(abort(anyhow!(
+            "{} conflicts with the remote, merge it with git in {}",
+            file,
+            dir.display()
+        )));
+    }
+
+    let mut reader = Reader {
+        dir,
+        parser: StoreParser::default(),
+    };
+    let mut conflicts = Vec::new();
+    for store in stores {
+        let (text, found) = reader.merge(base.trim(), store, prefer).map_err(abort)?;
+        if let Some(text) = text {
+            fs::write(dir.join(store), text).map_err(|e| abort(e.into()))?;
+            git(dir, &["add", "--", store]).map_err(abort)?;
+        }
+        conflicts.extend(found);
+    }
+    git(dir, &["commit", "--quiet", "--no-edit"]).map_err(abort)?;
+    Ok(conflicts)
+}
+
+/// Commits what changed in `stores` (paths of the stores, those outside the working tree
+/// are left out), then pulls from and pushes to the remote.
+pub fn sync(options: &SyncOptions, stores: &[String]) -> Result<Report> {
+    let dir = options.dir.as_path();
+    init(dir, &options.branch)?;
+    let stores = stores_in(dir, stores);
+    if stores.is_empty() {
+        bail!(
+            "no store is kept in {}, set data_path or layers.team to a file in it",
+            dir.display()
+        );
+    }
+    commit(dir, &stores, "tagcm: sync")?;
+
+    let remote = options
+        .remote
+        .as_deref()
+        .ok_or_else(|| anyhow!("set sync.remote to pull from and push to"))?;
+    let branch = format!("refs/heads/{}", options.branch);
+    let has_head = git_succeeds(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]);
+    let remote_has_branch = !git(dir, &["ls-remote", "--heads", remote, &branch])?
+        .trim()
+        .is_empty();
+    let mut report = Report::default();
+    if remote_has_branch {
+        git(dir, &["fetch", "--quiet", remote, &branch])?;
+        if !has_head {
+            // nothing here yet, take the remote as it is.
+            git(dir, &["reset", "--quiet", "--hard", "FETCH_HEAD"])?;
+            report.received = count(dir, "HEAD")?;
+        } else {
+            report.received = count(dir, "HEAD..FETCH_HEAD")?;
+            report.sent = count(dir, "FETCH_HEAD..HEAD")?;
+            match (report.received, report.sent) {
+                (0, _) => {}
+                (_, 0) => {
+                    git(dir, &["merge", "--quiet", "--ff-only", "FETCH_HEAD"])?;
+                }
+                _ => report.conflicts = merge_fetched(dir, &stores, options.prefer)?,
+            }
+        }
+    } else if has_head {
+        report.sent = count(dir, "HEAD")?;
+    }
+    if report.sent > 0 {
+        git(
+            dir,
+            &["push", "--quiet", remote, &format!("HEAD:{}", branch)],
+        )?;
+    }
+    Ok(report)
+}
+
+/// `sync` of the stores at `paths` with the settings of `config`.
+pub fn sync_stores(
+    config: &SyncConfig,
+    paths: &[String],
+    prefer: Option<Prefer>,
+) -> Result<Report> {
+    let dir = config
+        .dir
+        .clone()
+        .ok_or_else(|| anyhow!("set sync.dir to the git working tree the stores are kept in"))?;
+    let options = SyncOptions {
+        dir: PathBuf::from(dir),
+        remote: config.remote.clone(),
+        branch: config.branch.clone(),
+        prefer,
+    };
+    sync(&options, paths)
+}
+
+/// Commits the changes a command makes to the stores kept in a synced directory, one
+/// commit per command named after what it did.
+pub struct Recorder {
+    dir: PathBuf,
+    /// Each store with its journal and the last entry before the command.
+    stores: Vec<(String, Journal, Option<u64>)>,
+}
+
+impl Recorder {
+    /// Notes where the journals of `stores` (paths of the stores and the keys of the
+    /// encrypted ones) are, those outside `dir` are left out.
+    pub fn new(dir: &Path, stores: Vec<(String, Option<Cipher>)>) -> Result<Recorder> {
+        let mut recorded = Vec::new();
+        for (path, cipher) in stores {
+            if let Some(store) = stores_in(dir, std::slice::from_ref(&path)).pop() {
+                let journal = Journal::for_store(&path).with_cipher(cipher);
+                let last = journal.entries()?.last().map(|entry| entry.id);
+                recorded.push((store, journal, last));
+            }
+        }
+        Ok(Recorder {
+            dir: dir.to_path_buf(),
+            stores: recorded,
+        })
+    }
+
+    /// The recorder of the layers of `repo` kept under `sync.dir`, none without one.
+    pub fn for_layers(config: &SyncConfig, repo: &LayeredRepository) -> Result<Option<Recorder>> {
+        let Some(dir) = &config.dir else {
+            return Ok(None);
+        };
+        let mut stores = Vec::new();
+        for name in repo.layer_names() {
+            let layer = repo.layer(&name)?;
+            if let Some(path) = layer.file_path() {
+                stores.push((path, layer.cipher()));
+            }
+        }
+        Recorder::new(Path::new(dir), stores).map(Some)
+    }
+
+    /// Commits the stores the command changed, when the directory is a git working tree.
+    pub fn commit(&self) -> Result<()> {
+        if !is_repository(&self.dir) {
+            return Ok(());
+        }
+        let mut titles = Vec::new();
+        let mut changed = Vec::new();
+        for (store, journal, last) in &self.stores {
+            let entries = journal.entries()?;
+            let new: Vec<String> = entries
+                .iter()
+                .filter(|entry| Some(entry.id) > *last)
+                .map(|entry| entry.title())
+                .collect();
+            if !new.is_empty() {
+                titles.extend(new);
+                changed.push(store.clone());
+            }
+        }
+        if !changed.is_empty() {
+            commit(
+                &self.dir,
+                &changed,
+                &format!("tagcm: {}", titles.join("; ")),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stores_in() {
+        let dir = std::env::temp_dir().join(format!("tagcm_sync_{}", std::process::id()));
+        fs::create_dir_all(dir.join("team")).unwrap();
+        let inside = dir.join("team").join("tags.json").display().to_string();
+        let outside = std::env::temp_dir().join("tags.json").display().to_string();
+        assert_eq!(stores_in(&dir, &[inside, outside]), vec!["team/tags.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = std::env::temp_dir().join(format!("tagcm_sync_read_{}", std::process::id()));
+        init(&dir, "main").unwrap();
+        git(&dir, &["config", "user.name", "tagcm"]).unwrap();
+        git(&dir, &["config", "user.email", "tagcm@localhost"]).unwrap();
+        fs::write(dir.join("tags.json"), r#"{"build": "make"}"#).unwrap();
+        commit(&dir, &["tags.json".to_string()], "add").unwrap();
+
+        let mut reader = Reader {
+            dir: &dir,
+            parser: StoreParser::default(),
+        };
+        let (tags, cipher) = reader.read("HEAD", "tags.json").unwrap();
+        assert_eq!(tags["build"], "make");
+        assert!(cipher.is_none());
+        // a store not there yet has no tags, a revision that is not there is an error.
+        assert!(reader.read("HEAD", "team.json").unwrap().0.is_empty());
+        assert!(reader.read("FETCH_HEAD", "tags.json").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
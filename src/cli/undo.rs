@@ -0,0 +1,94 @@
+use super::secret::SecretRules;
+use crate::repo::journal::{Entry, Journal};
+use crate::repo::{error::RepoError, tag_data_repository::TagDataRepository};
+
+fn describe(verb: &str, entry: &Entry, secrets: Option<&SecretRules>) -> String {
+    let ops: Vec<String> = entry
+        .ops()
+        .iter()
+        .map(|op| {
+            let mut op = op.clone();
+            if let Some(secrets) = secrets {
+                secrets.mask_op(&mut op);
+            }
+            op.to_string()
+        })
+        .collect();
+    format!("{} #{}: {}", verb, entry.id, ops.join(", "))
+}
+
+/// Takes back the last change to `repo` and tells what it was, the secrets masked with
+/// `secrets` when given.
+pub fn undo<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    secrets: Option<&SecretRules>,
+) -> Result<String, RepoError> {
+    Ok(match journal.undo(repo)? {
+        Some(entry) => describe("Undid", &entry, secrets),
+        None => "Nothing to undo".to_string(),
+    })
+}
+
+/// Applies the last undone change to `repo` again and tells what it was, like `undo`.
+pub fn redo<T: TagDataRepository + ?Sized>(
+    repo: &mut T,
+    journal: &Journal,
+    secrets: Option<&SecretRules>,
+) -> Result<String, RepoError> {
+    Ok(match journal.redo(repo)? {
+        Some(entry) => describe("Redid", &entry, secrets),
+        None => "Nothing to redo".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::add::add;
+    use crate::cli::unittest_repository::{temporary_journal, UnitTestRepository};
+    #[test]
+    fn test_undo_redo() {
+        let mut repo = UnitTestRepository::new();
+        let journal = temporary_journal("cmd_undo");
+        assert_eq!(undo(&mut repo, &journal, None).unwrap(), "Nothing to undo");
+        add(
+            "test".to_string(),
+            "echo test".to_string(),
+            &mut repo,
+            &journal,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            undo(&mut repo, &journal, None).unwrap(),
+            "Undid #1: add test: echo test"
+        );
+        assert_eq!(repo.get_tag_data("test"), None);
+        assert_eq!(
+            redo(&mut repo, &journal, None).unwrap(),
+            "Redid #1: add test: echo test"
+        );
+        assert_eq!(repo.get_tag_data("test").unwrap(), "echo test");
+        assert_eq!(redo(&mut repo, &journal, None).unwrap(), "Nothing to redo");
+
+        let secrets = SecretRules::default();
+        add(
+            "test".to_string(),
+            "TOKEN=abc make".to_string(),
+            &mut repo,
+            &journal,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            undo(&mut repo, &journal, Some(&secrets)).unwrap(),
+            "Undid #4: replace test: TOKEN=******** make (was: echo test)"
+        );
+        assert_eq!(
+            redo(&mut repo, &journal, Some(&secrets)).unwrap(),
+            "Redid #4: replace test: TOKEN=******** make (was: echo test)"
+        );
+        std::fs::remove_file(journal.path()).unwrap();
+    }
+}
@@ -0,0 +1,493 @@
+mod changelog;
+mod download;
+mod install;
+pub mod notice;
+mod platform;
+mod semver;
+mod verify;
+
+use crate::config::{Channel, ASSET_URL_TEMPLATE, COMMAND_NAME, RELEASES_API_URL};
+use anyhow::{anyhow, bail, Error, Result};
+use directories::BaseDirs;
+use download::Downloader;
+use flate2::read::GzDecoder;
+use platform::Platform;
+use semver::Semver;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tar::Archive;
+
+#[derive(Clone, Debug)]
+pub struct UpdateOptions {
+    /// Refuse to install a release without a valid signature.
+    pub require_signature: bool,
+    /// GitHub compatible releases API, `<api_url>/latest` is the latest release.
+    pub api_url: String,
+    /// Download URL of a release asset, `{version}` and `{asset}` are replaced.
+    pub asset_url_template: String,
+    pub channel: Channel,
+    /// Install without asking.
+    pub assume_yes: bool,
+    /// Install this release instead of the latest one, which may be older than the running one.
+    pub target_version: Option<String>,
+    /// Proxy for every request, HTTPS_PROXY and friends are used when it is not set.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates, e.g. of a TLS intercepting proxy.
+    pub ca_bundle: Option<PathBuf>,
+    pub connect_timeout: Duration,
+    /// Longest wait for the server to answer or to send more of a download.
+    pub read_timeout: Duration,
+    /// How often a request that failed on the way is tried again.
+    pub retries: u32,
+    /// Install an archive from disk that has neither a checksum nor a signature to check.
+    pub skip_verify: bool,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        UpdateOptions {
+            require_signature: false,
+            api_url: RELEASES_API_URL.to_string(),
+            asset_url_template: ASSET_URL_TEMPLATE.to_string(),
+            channel: Channel::default(),
+            assume_yes: false,
+            target_version: None,
+            proxy: None,
+            ca_bundle: None,
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(30),
+            retries: 3,
+            skip_verify: false,
+        }
+    }
+}
+
+impl UpdateOptions {
+    fn asset_url(&self, tag: &str, asset: &str) -> String {
+        self.asset_url_template
+            .replace("{version}", tag)
+            .replace("{asset}", asset)
+    }
+}
+
+#[derive(Debug)]
+struct DownloadBinary {
+    url: String,
+}
+
+impl DownloadBinary {
+    // mirrors may not list the assets of a release, they get the name the release workflow uses.
+    fn new(assets: &[String]) -> Result<Self, Error> {
+        let platform = Platform::current();
+        let url = if assets.is_empty() {
+            platform.default_asset_name()?
+        } else {
+            platform.select_asset(assets)?.to_string()
+        };
+        Ok(DownloadBinary { url })
+    }
+
+    fn get_url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn get_binary_name(&self) -> String {
+        let stem = self.url.trim_end_matches(".tar.gz");
+        stem.trim_end_matches(".exe").to_string()
+    }
+
+    // published by the release workflow next to the archive, named after the binary.
+    fn get_checksum_name(&self) -> String {
+        format!("{}.sha256", self.url.trim_end_matches(".tar.gz"))
+    }
+
+    fn get_signature_name(&self) -> String {
+        format!("{}.sig", self.url)
+    }
+}
+
+/// `checksum` is `None` when there is nothing to check against, which only local archives allow.
+fn verify_archive(
+    archive: &[u8],
+    checksum: Option<Result<String>>,
+    signature: Result<String>,
+    options: &UpdateOptions,
+) -> Result<()> {
+    if let Some(checksum) = checksum {
+        let checksum = checksum.map_err(|e| anyhow!("failed to get the checksum: {}", e))?;
+        verify::verify_checksum(archive, &checksum)?;
+        println!("checksum verified");
+    }
+
+    match (verify::RELEASE_PUBLIC_KEY, signature) {
+        (Some(public_key), Ok(signature)) => {
+            verify::verify_signature(archive, &signature, public_key)?;
+            println!("signature verified");
+        }
+        (None, _) if options.require_signature => {
+            return Err(anyhow!(
+                "this build has no release public key to verify with"
+            ));
+        }
+        (_, Err(e)) if options.require_signature => {
+            return Err(anyhow!("failed to get the signature: {}", e));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    if !super::confirm(question)? {
+        println!("update canceled");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// an archive holds a single binary, whatever it is called.
+fn find_binary(dir: &Path, expected_name: &str) -> Result<PathBuf> {
+    let expected = dir.join(expected_name);
+    if expected.is_file() {
+        return Ok(expected);
+    }
+    let files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    match files.as_slice() {
+        [binary] => Ok(binary.clone()),
+        _ => Err(anyhow!("no binary named {} in the archive", expected_name)),
+    }
+}
+
+fn install_archive(archive: &[u8], binary_name: &str, version: &str) -> Result<()> {
+    let staging = install::StagingDir::new()?;
+    Archive::new(GzDecoder::new(archive)).unpack(staging.path())?;
+    let exe = env::current_exe()?;
+    match find_binary(staging.path(), binary_name)
+        .and_then(|binary| install::install(&binary, &exe))
+    {
+        Err(e) => {
+            println!("failed to install the new binary: {}", e);
+            return Err(e);
+        }
+        Ok(_) => {
+            println!("Successfully updated to {}!", version);
+            println!(
+                "the previous version is kept at {}, run `tagcm update --rollback` to restore it",
+                install::backup_path(&exe)?.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+// kept across runs, so an interrupted download is resumed by the next `update`.
+fn part_path(tag: &str, asset: &str) -> PathBuf {
+    BaseDirs::new()
+        .map(|base_dir| base_dir.cache_dir().to_path_buf())
+        .unwrap_or_else(env::temp_dir)
+        .join(COMMAND_NAME)
+        .join("downloads")
+        .join(format!("{}-{}.part", tag, asset))
+}
+
+/// A release as the GitHub releases API describes it, only the fields `update` reads.
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    assets: Vec<Asset>,
+    name: Option<String>,
+    /// Release notes, markdown.
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+}
+
+impl Release {
+    fn version(&self) -> Result<Semver> {
+        Semver::new(&self.tag_name)
+            .map_err(|e| anyhow!("release {} has no valid version: {}", self.tag_name, e))
+    }
+
+    fn asset_names(&self) -> Vec<String> {
+        self.assets.iter().map(|asset| asset.name.clone()).collect()
+    }
+}
+
+// the most recent releases, newer than anything an update goes past in practice.
+fn fetch_releases(downloader: &Downloader, options: &UpdateOptions) -> Result<Vec<Release>> {
+    downloader.json(&format!(
+        "{}?per_page=100",
+        options.api_url.trim_end_matches('/')
+    ))
+}
+
+fn show_changelog(
+    downloader: &Downloader,
+    options: &UpdateOptions,
+    current: &Semver,
+    target: &Semver,
+) {
+    match fetch_releases(downloader, options) {
+        Ok(releases) => {
+            let between = changelog::releases_between(&releases, current, target);
+            if !between.is_empty() {
+                println!("changes since {}:\n", current);
+                changelog::show(&changelog::render(&between), !options.assume_yes);
+            }
+        }
+        Err(e) => println!("could not fetch the release notes: {}", e),
+    }
+}
+
+// `<api_url>/tags/<version>` of a GitHub compatible releases API.
+fn fetch_release(
+    downloader: &Downloader,
+    options: &UpdateOptions,
+    version: &Semver,
+) -> Result<Release> {
+    let url = format!("{}/tags/{}", options.api_url.trim_end_matches('/'), version);
+    downloader.json(&url)
+}
+
+fn fetch_latest_release(
+    downloader: &Downloader,
+    options: &UpdateOptions,
+) -> Result<(Semver, Release)> {
+    let api_url = options.api_url.trim_end_matches('/');
+    match options.channel {
+        Channel::Stable => {
+            let release: Release = downloader.json(&format!("{}/latest", api_url))?;
+            let version = release.version()?;
+            if release.prerelease || version.is_prerelease() {
+                return Err(anyhow!(
+                    "the latest release {} is a pre-release",
+                    release.tag_name
+                ));
+            }
+            Ok((version, release))
+        }
+        Channel::Beta => {
+            let releases = fetch_releases(downloader, options)?;
+            releases
+                .into_iter()
+                .filter(|release| !release.draft)
+                .filter_map(|release| release.version().ok().map(|version| (version, release)))
+                .max_by(|a, b| a.0.cmp(&b.0))
+                .ok_or_else(|| anyhow!("no release found"))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct UpdateStatus {
+    pub current: String,
+    pub latest: String,
+    pub update_available: bool,
+}
+
+/// Compares the running version with the latest release without installing anything.
+pub fn check(current_version: &str, options: &UpdateOptions) -> Result<UpdateStatus> {
+    let (latest_version, _) = fetch_latest_release(&Downloader::new(options)?, options)?;
+    let current_version = Semver::new(current_version)?;
+    Ok(UpdateStatus {
+        update_available: latest_version > current_version,
+        current: current_version.to_string(),
+        latest: latest_version.to_string(),
+    })
+}
+
+pub fn update(current_version: &str, options: &UpdateOptions) -> Result<()> {
+    let downloader = Downloader::new(options)?;
+    let current_version = Semver::new(current_version)?;
+    println!("current version: \"{}\"", current_version);
+
+    let (latest_version, release) = match &options.target_version {
+        Some(version) => {
+            let version = Semver::new(version)?;
+            let release = fetch_release(&downloader, options, &version)
+                .map_err(|e| anyhow!("release {} not found: {}", version, e))?;
+            let found = release.version()?;
+            println!("requested version: \"{}\"", found);
+            if found == current_version {
+                println!("{} is already installed", found);
+                return Ok(());
+            }
+            (found, release)
+        }
+        None => {
+            let (latest_version, release) = fetch_latest_release(&downloader, options)?;
+            println!("latest version: \"{}\"", latest_version);
+            if latest_version <= current_version {
+                println!("this is the latest version");
+                return Ok(());
+            }
+            (latest_version, release)
+        }
+    };
+    let download_binary = DownloadBinary::new(&release.asset_names())?;
+    show_changelog(&downloader, options, &current_version, &latest_version);
+
+    let question = if latest_version < current_version {
+        format!("downgrade to {}?", latest_version)
+    } else {
+        format!("latest version is available, update to {}?", latest_version)
+    };
+    if !options.assume_yes && !confirm(&question)? {
+        return Ok(());
+    }
+
+    let asset_url = |asset: &str| options.asset_url(&release.tag_name, asset);
+    let archive = downloader.download(
+        &asset_url(&download_binary.get_url()),
+        &part_path(&release.tag_name, &download_binary.get_url()),
+    )?;
+    if let Err(e) = verify_archive(
+        &archive,
+        Some(downloader.text(&asset_url(&download_binary.get_checksum_name()))),
+        downloader.text(&asset_url(&download_binary.get_signature_name())),
+        options,
+    ) {
+        println!("refusing to install {}: {}", latest_version, e);
+        return Err(e);
+    }
+
+    install_archive(
+        &archive,
+        &download_binary.get_binary_name(),
+        &latest_version.to_string(),
+    )
+}
+
+/// Installs a release archive from disk, verified against the `.sha256` file and the
+/// signature next to it. One without either is only installed with `skip_verify`.
+pub fn update_from_file(path: &Path, options: &UpdateOptions) -> Result<()> {
+    let archive = std::fs::read(path)?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("invalid archive path: {}", path.display()))?;
+    let local = DownloadBinary { url: file_name };
+
+    let checksum_path = path.with_file_name(local.get_checksum_name());
+    let checksum = checksum_path
+        .exists()
+        .then(|| std::fs::read_to_string(&checksum_path).map_err(Error::from));
+    let signature = std::fs::read_to_string(path.with_file_name(local.get_signature_name()))
+        .map_err(Error::from);
+    let signed = verify::RELEASE_PUBLIC_KEY.is_some() && signature.is_ok();
+    if checksum.is_none() && !signed {
+        if !options.skip_verify {
+            println!(
+                "refusing to install {}: no checksum found at {} and no signature to check",
+                path.display(),
+                checksum_path.display()
+            );
+            bail!(
+                "{} can not be verified, use --skip-verify to install it anyway",
+                path.display()
+            );
+        }
+        println!("installing {} without verification", path.display());
+    }
+    if let Err(e) = verify_archive(&archive, checksum, signature, options) {
+        println!("refusing to install {}: {}", path.display(), e);
+        return Err(e);
+    }
+
+    if !options.assume_yes && !confirm(&format!("install {}?", path.display()))? {
+        return Ok(());
+    }
+    install_archive(
+        &archive,
+        &local.get_binary_name(),
+        &path.display().to_string(),
+    )
+}
+
+/// Restores the binary replaced by the last update.
+pub fn rollback() -> Result<()> {
+    let exe = env::current_exe()?;
+    install::rollback(&exe)?;
+    println!("Successfully restored the previous version");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_release_json() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name": "v1.2.0-beta.1", "name": "v1.1.9 fixes", "prerelease": true, "body": "v0.0.1",
+                "assets": [{"name": "tagcm-x86_64-unknown-linux-gnu.tar.gz", "size": 10}]}"#,
+        )
+        .unwrap();
+        assert!(release.prerelease);
+        assert!(!release.draft);
+        assert_eq!(release.version().unwrap().to_string(), "v1.2.0-beta.1");
+        assert_eq!(
+            release.asset_names(),
+            vec!["tagcm-x86_64-unknown-linux-gnu.tar.gz"]
+        );
+
+        let release: Release = serde_json::from_str(r#"{"tag_name": "nightly"}"#).unwrap();
+        assert!(release.version().is_err());
+    }
+
+    #[test]
+    fn test_download_binary() {
+        let download_binary = DownloadBinary::new(&[]).unwrap();
+        assert_eq!(
+            download_binary.get_url(),
+            Platform::current().default_asset_name().unwrap()
+        );
+
+        let download_binary = DownloadBinary {
+            url: "tagcm-v1.2.0-x86_64-pc-windows-msvc.exe.tar.gz".to_string(),
+        };
+        assert_eq!(
+            download_binary.get_binary_name(),
+            "tagcm-v1.2.0-x86_64-pc-windows-msvc"
+        );
+    }
+
+    #[test]
+    fn test_asset_url() {
+        let options = UpdateOptions {
+            asset_url_template: "http://mirror.local/tagcm/{version}/{asset}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.asset_url("v1.2.3", "tagcm.tar.gz"),
+            "http://mirror.local/tagcm/v1.2.3/tagcm.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_release_asset_names() {
+        let download_binary = DownloadBinary {
+            url: "tagcm-x86_64-pc-windows-msvc.exe.tar.gz".to_string(),
+        };
+        assert_eq!(
+            download_binary.get_checksum_name(),
+            "tagcm-x86_64-pc-windows-msvc.exe.sha256"
+        );
+        assert_eq!(
+            download_binary.get_signature_name(),
+            "tagcm-x86_64-pc-windows-msvc.exe.tar.gz.sig"
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use super::semver::Semver;
+use super::Release;
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// The releases an update from `current` to `target` brings in, newest first.
+/// Pre-releases are left out unless `target` is one.
+pub fn releases_between<'a>(
+    releases: &'a [Release],
+    current: &Semver,
+    target: &Semver,
+) -> Vec<(Semver, &'a Release)> {
+    let mut between: Vec<(Semver, &Release)> = releases
+        .iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| release.version().ok().map(|version| (version, release)))
+        .filter(|(version, _)| version > current && version <= target)
+        .filter(|(version, _)| target.is_prerelease() || !version.is_prerelease())
+        .collect();
+    between.sort_by(|a, b| b.0.cmp(&a.0));
+    between
+}
+
+pub fn render(releases: &[(Semver, &Release)]) -> String {
+    let mut text = String::new();
+    for (version, release) in releases {
+        match release.name.as_deref().filter(|name| !name.is_empty()) {
+            Some(name) if name != release.tag_name => {
+                text.push_str(&format!("## {} - {}\n\n", version, name))
+            }
+            _ => text.push_str(&format!("## {}\n\n", version)),
+        }
+        let body = release
+            .body
+            .as_deref()
+            .unwrap_or_default()
+            .replace("\r\n", "\n");
+        match body.trim() {
+            "" => text.push_str("(no release notes)\n\n"),
+            body => text.push_str(&format!("{}\n\n", body)),
+        }
+    }
+    text
+}
+
+/// Prints `text`, through `$PAGER` (`less` by default) when `paged` and stdout is a terminal.
+pub fn show(text: &str, paged: bool) {
+    if !paged || !std::io::stdout().is_terminal() || !page(text) {
+        print!("{}", text);
+    }
+}
+
+// false when the pager could not be run, the text is printed instead.
+fn page(text: &str) -> bool {
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut args = pager.split_whitespace();
+    let Some(program) = args.next() else {
+        return false;
+    };
+    let Ok(mut child) = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+    else {
+        return false;
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything.
+        _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn releases() -> Vec<Release> {
+        serde_json::from_str(
+            r#"[
+                {"tag_name": "v1.3.0-beta.1", "prerelease": true, "body": "beta"},
+                {"tag_name": "v1.2.0", "name": "Layers", "body": "* layered catalogs\r\n* fixes"},
+                {"tag_name": "v1.1.1", "name": "v1.1.1", "body": ""},
+                {"tag_name": "v1.1.0", "draft": true, "body": "draft"},
+                {"tag_name": "v1.0.0", "body": "first"}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn tags(releases: &[(Semver, &Release)]) -> Vec<String> {
+        releases.iter().map(|(v, _)| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_releases_between() {
+        let releases = releases();
+        let current = Semver::new("v1.0.0").unwrap();
+
+        let between = releases_between(&releases, &current, &Semver::new("v1.2.0").unwrap());
+        assert_eq!(tags(&between), vec!["v1.2.0", "v1.1.1"]);
+
+        let between = releases_between(&releases, &current, &Semver::new("v1.3.0-beta.1").unwrap());
+        assert_eq!(tags(&between), vec!["v1.3.0-beta.1", "v1.2.0", "v1.1.1"]);
+
+        let between = releases_between(&releases, &Semver::new("v1.2.0").unwrap(), &current);
+        assert!(between.is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let releases = releases();
+        let between = releases_between(
+            &releases,
+            &Semver::new("v1.0.0").unwrap(),
+            &Semver::new("v1.2.0").unwrap(),
+        );
+        assert_eq!(
+            render(&between),
+            "## v1.2.0 - Layers\n\n* layered catalogs\n* fixes\n\n## v1.1.1\n\n(no release notes)\n\n"
+        );
+    }
+}
@@ -0,0 +1,314 @@
+use super::UpdateOptions;
+use anyhow::{anyhow, Context, Error, Result};
+use reqwest::blocking::{Client, Response};
+use reqwest::{header, Certificate, NoProxy, Proxy, StatusCode};
+use serde::de::DeserializeOwned;
+use std::fs::{self, File, OpenOptions};
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Wait before the first retry, doubled for every further one.
+const BACKOFF: Duration = Duration::from_secs(1);
+const CHUNK_SIZE: usize = 64 * 1024;
+const PROGRESS_WIDTH: usize = 30;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// HTTP access for `update`: proxy and CA settings, timeouts and retries.
+pub struct Downloader {
+    client: Client,
+    retries: u32,
+    proxy: Option<String>,
+}
+
+impl Downloader {
+    pub fn new(options: &UpdateOptions) -> Result<Self> {
+        // `timeout` of the blocking client also bounds every read of a response body.
+        let mut builder = Client::builder()
+            .use_rustls_tls()
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.read_timeout);
+        // without an explicit proxy, reqwest reads HTTPS_PROXY, HTTP_PROXY, ALL_PROXY and NO_PROXY.
+        if let Some(proxy) = &options.proxy {
+            let proxy = Proxy::all(proxy)
+                .with_context(|| format!("invalid proxy url {}", proxy))?
+                .no_proxy(NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = &options.ca_bundle {
+            let pem = fs::read(path)
+                .with_context(|| format!("failed to read the CA bundle {}", path.display()))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("invalid CA bundle {}", path.display()))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(Downloader {
+            client: builder.build()?,
+            retries: options.retries,
+            proxy: options.proxy.clone().or_else(proxy_from_env),
+        })
+    }
+
+    fn get(&self, url: &str, offset: u64) -> Result<Response, reqwest::Error> {
+        let mut request = self
+            .client
+            .get(url)
+            .header(header::USER_AGENT, "tagcm")
+            .header(header::ACCEPT, "application/vnd.github+json");
+        if offset > 0 {
+            request = request.header(header::RANGE, format!("bytes={}-", offset));
+        }
+        request.send()?.error_for_status()
+    }
+
+    // runs `attempt` until it succeeds, fails for good or runs out of retries.
+    fn retry<T>(&self, url: &str, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut tries = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(e) if tries < self.retries && is_transient(&e) => {
+                    let wait = BACKOFF * 2u32.pow(tries);
+                    tries += 1;
+                    eprintln!(
+                        "{}, retrying in {}s ({}/{})",
+                        self.describe(&e, url),
+                        wait.as_secs(),
+                        tries,
+                        self.retries
+                    );
+                    thread::sleep(wait);
+                }
+                Err(e) => {
+                    let message = self.describe(&e, url);
+                    return Err(e.context(message));
+                }
+            }
+        }
+    }
+
+    // reqwest errors say little about what to do about them.
+    fn describe(&self, e: &Error, url: &str) -> String {
+        let via = match &self.proxy {
+            Some(proxy) => format!(" through the proxy {}", proxy),
+            None => String::new(),
+        };
+        match e.downcast_ref::<reqwest::Error>() {
+            Some(e) if e.status().is_some() => {
+                format!("{} answered {}", url, e.status().unwrap())
+            }
+            Some(e) if e.is_timeout() => format!(
+                "timed out talking to {}{}, see update.connect_timeout_secs and update.read_timeout_secs",
+                url, via
+            ),
+            Some(e) if e.is_connect() => format!("failed to connect to {}{}", url, via),
+            _ => format!("failed to download {}{}", url, via),
+        }
+    }
+
+    pub fn text(&self, url: &str) -> Result<String> {
+        self.retry(url, || Ok(self.get(url, 0)?.text()?))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let body = self.text(url)?;
+        serde_json::from_str(&body).map_err(|e| anyhow!("unexpected response from {}: {}", url, e))
+    }
+
+    /// Downloads `url` into `part` and returns its content. What an earlier, interrupted
+    /// download left in `part` is resumed when the server supports ranges.
+    pub fn download(&self, url: &str, part: &Path) -> Result<Vec<u8>> {
+        if let Some(parent) = part.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(part)?;
+        self.retry(url, || self.download_into(url, &mut file, part))
+            .map_err(|e| e.context(format!("partial download kept at {}", part.display())))?;
+
+        let mut archive = Vec::new();
+        File::open(part)?.read_to_end(&mut archive)?;
+        _ = fs::remove_file(part);
+        Ok(archive)
+    }
+
+    fn download_into(&self, url: &str, file: &mut File, part: &Path) -> Result<()> {
+        let offset = file.metadata()?.len();
+        let mut resp = match self.get(url, offset) {
+            // what was kept is complete, or not part of this file any more.
+            Err(e) if e.status() == Some(StatusCode::RANGE_NOT_SATISFIABLE) => {
+                file.set_len(0)?;
+                self.get(url, 0)?
+            }
+            other => other?,
+        };
+        let mut done = if resp.status() == StatusCode::PARTIAL_CONTENT {
+            offset
+        } else {
+            file.set_len(0)?;
+            0
+        };
+        if done > 0 {
+            eprintln!("resuming the download at {}", format_bytes(done));
+        }
+        let total = resp.content_length().map(|length| done + length);
+
+        let mut progress = Progress::new(done, total);
+        let mut buf = vec![0; CHUNK_SIZE];
+        let result = loop {
+            let read = match resp.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(read) => read,
+                Err(e) => break Err(body_error(e)),
+            };
+            // not an io::Error any more, so it is not retried.
+            if let Err(e) = file.write_all(&buf[..read]) {
+                break Err(anyhow!("failed to write {}: {}", part.display(), e));
+            }
+            done += read as u64;
+            progress.update(done);
+        };
+        progress.finish();
+        result?;
+        match total {
+            Some(total) if done < total => Err(Error::new(std::io::Error::from(
+                std::io::ErrorKind::UnexpectedEof,
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn proxy_from_env() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+// reading the body of a blocking response fails with an io::Error around the reqwest one.
+fn body_error(e: std::io::Error) -> Error {
+    if e.get_ref()
+        .is_some_and(|inner| inner.is::<reqwest::Error>())
+    {
+        let inner = e
+            .into_inner()
+            .unwrap()
+            .downcast::<reqwest::Error>()
+            .unwrap();
+        return Error::new(*inner);
+    }
+    Error::new(e)
+}
+
+fn is_transient(e: &Error) -> bool {
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return match e.status() {
+            Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+            None => e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+        };
+    }
+    // a connection that broke off while reading the body.
+    e.is::<std::io::Error>()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// One line of download progress, redrawn on stderr while it is a terminal.
+struct Progress {
+    start: Instant,
+    start_bytes: u64,
+    total: Option<u64>,
+    last_draw: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    fn new(done: u64, total: Option<u64>) -> Self {
+        Progress {
+            start: Instant::now(),
+            start_bytes: done,
+            total,
+            last_draw: None,
+            enabled: std::io::stderr().is_terminal(),
+        }
+    }
+
+    fn update(&mut self, done: u64) {
+        let now = Instant::now();
+        if !self.enabled
+            || self
+                .last_draw
+                .is_some_and(|last| now - last < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        let rate = (done - self.start_bytes) as f64 / (now - self.start).as_secs_f64().max(0.001);
+        eprint!("\r{}", render_progress(done, self.total, rate as u64));
+    }
+
+    fn finish(&mut self) {
+        if self.enabled && self.last_draw.is_some() {
+            eprintln!();
+        }
+        self.enabled = false;
+    }
+}
+
+fn render_progress(done: u64, total: Option<u64>, rate: u64) -> String {
+    match total {
+        Some(total) if total > 0 => {
+            let filled = (PROGRESS_WIDTH as u64 * done.min(total) / total) as usize;
+            format!(
+                "[{}{}] {} / {} {}/s",
+                "#".repeat(filled),
+                " ".repeat(PROGRESS_WIDTH - filled),
+                format_bytes(done),
+                format_bytes(total),
+                format_bytes(rate)
+            )
+        }
+        _ => format!("{} {}/s", format_bytes(done), format_bytes(rate)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn test_render_progress() {
+        assert_eq!(
+            render_progress(512, Some(1024), 2048),
+            format!(
+                "[{}{}] 512 B / 1.0 KiB 2.0 KiB/s",
+                "#".repeat(15),
+                " ".repeat(15)
+            )
+        );
+        assert_eq!(render_progress(1536, None, 0), "1.5 KiB 0 B/s");
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A private directory for the download, made under a random name and readable by the
+/// user only, removed with everything in it on drop.
+pub struct StagingDir {
+    dir: tempfile::TempDir,
+}
+
+impl StagingDir {
+    pub fn new() -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("tagcm-update-")
+            .tempdir()
+            .context("failed to create the staging directory")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(StagingDir { dir })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+fn sibling(exe: &Path, suffix: &str) -> Result<PathBuf> {
+    let name = exe
+        .file_name()
+        .ok_or_else(|| anyhow!("invalid executable path: {}", exe.display()))?;
+    Ok(exe.with_file_name(format!("{}{}", name.to_string_lossy(), suffix)))
+}
+
+/// Where the replaced binary is kept, next to the executable.
+pub fn backup_path(exe: &Path) -> Result<PathBuf> {
+    sibling(exe, ".bak")
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn keep_copy(from: &Path, to: &Path) -> Result<()> {
+    if to.exists() {
+        fs::remove_file(to)?;
+    }
+    if fs::hard_link(from, to).is_err() {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+// windows cannot replace a running executable, but it can move it out of the way.
+#[cfg(windows)]
+fn swap_into_place(new: &Path, exe: &Path, backup: &Path) -> Result<()> {
+    if backup.exists() {
+        fs::remove_file(backup)?;
+    }
+    fs::rename(exe, backup)?;
+    if let Err(e) = fs::rename(new, exe) {
+        fs::rename(backup, exe)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn swap_into_place(new: &Path, exe: &Path, backup: &Path) -> Result<()> {
+    keep_copy(exe, backup)?;
+    fs::rename(new, exe)?;
+    Ok(())
+}
+
+/// Replaces `exe` with `new_binary`, keeping the current one as the backup.
+/// `exe` is either the old or the new binary at any point, never missing.
+pub fn install(new_binary: &Path, exe: &Path) -> Result<()> {
+    // a rename is only atomic within one file system, so stage next to the executable.
+    let staged = sibling(exe, ".new")?;
+    fs::copy(new_binary, &staged)?;
+    make_executable(&staged)?;
+    let result = swap_into_place(&staged, exe, &backup_path(exe)?);
+    if result.is_err() {
+        _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+/// Puts the backup back in place, the binary it replaces becomes the new backup.
+pub fn rollback(exe: &Path) -> Result<()> {
+    let backup = backup_path(exe)?;
+    if !backup.exists() {
+        return Err(anyhow!("no previous version found at {}", backup.display()));
+    }
+    let previous = sibling(exe, ".old")?;
+    keep_copy(&backup, &previous)?;
+    let result = install(&previous, exe);
+    _ = fs::remove_file(&previous);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (StagingDir, PathBuf) {
+        let dir = StagingDir::new().unwrap();
+        let exe = dir.path().join("tagcm");
+        fs::write(&exe, "v1").unwrap();
+        (dir, exe)
+    }
+
+    #[test]
+    fn test_staging_dir() {
+        let (dir, other) = (StagingDir::new().unwrap(), StagingDir::new().unwrap());
+        assert_ne!(dir.path(), other.path());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        let path = dir.path().to_path_buf();
+        fs::write(path.join("archive"), "data").unwrap();
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_install_and_rollback() {
+        let (dir, exe) = setup();
+        let new_binary = dir.path().join("tagcm-x86_64-unknown-linux-gnu");
+        fs::write(&new_binary, "v2").unwrap();
+
+        install(&new_binary, &exe).unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "v2");
+        assert_eq!(
+            fs::read_to_string(backup_path(&exe).unwrap()).unwrap(),
+            "v1"
+        );
+        assert!(!sibling(&exe, ".new").unwrap().exists());
+
+        rollback(&exe).unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "v1");
+        assert_eq!(
+            fs::read_to_string(backup_path(&exe).unwrap()).unwrap(),
+            "v2"
+        );
+
+        rollback(&exe).unwrap();
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "v2");
+    }
+
+    #[test]
+    fn test_rollback_without_backup() {
+        let (_dir, exe) = setup();
+        assert!(rollback(&exe).is_err());
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "v1");
+    }
+
+    #[test]
+    fn test_install_missing_binary_keeps_exe() {
+        let (dir, exe) = setup();
+        assert!(install(&dir.path().join("nothing"), &exe).is_err());
+        assert_eq!(fs::read_to_string(&exe).unwrap(), "v1");
+    }
+}
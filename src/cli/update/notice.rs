@@ -0,0 +1,112 @@
+use super::{check, UpdateOptions};
+use crate::config::COMMAND_NAME;
+use directories::BaseDirs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const STAMP_FILE_NAME: &str = "last_update_check";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A release check running next to another command, see `spawn`.
+pub struct Notice {
+    receiver: Receiver<Option<String>>,
+}
+
+impl Notice {
+    /// The one-line notice when a newer release was found within `timeout`.
+    pub fn wait(self, timeout: Duration) -> Option<String> {
+        self.receiver.recv_timeout(timeout).ok().flatten()
+    }
+}
+
+pub fn stamp_path() -> Option<PathBuf> {
+    BaseDirs::new().map(|base_dir| {
+        base_dir
+            .cache_dir()
+            .join(COMMAND_NAME)
+            .join(STAMP_FILE_NAME)
+    })
+}
+
+fn seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn is_due(stamp: &Path, interval: Duration, now: SystemTime) -> bool {
+    match fs::read_to_string(stamp).map(|text| text.trim().parse::<u64>()) {
+        Ok(Ok(last)) => seconds(now).saturating_sub(last) >= interval.as_secs(),
+        _ => true,
+    }
+}
+
+fn record(stamp: &Path, now: SystemTime) {
+    if let Some(parent) = stamp.parent() {
+        _ = fs::create_dir_all(parent);
+    }
+    _ = fs::write(stamp, seconds(now).to_string());
+}
+
+/// Starts checking for a new release in the background when the last check is older than `interval`.
+pub fn spawn(
+    current_version: &str,
+    options: &UpdateOptions,
+    interval: Duration,
+    stamp: &Path,
+) -> Option<Notice> {
+    let now = SystemTime::now();
+    if !is_due(stamp, interval, now) {
+        return None;
+    }
+    // recorded up front, an unreachable server should not slow down every command.
+    record(stamp, now);
+
+    let (sender, receiver) = mpsc::channel();
+    let current_version = current_version.to_string();
+    let options = UpdateOptions {
+        connect_timeout: CHECK_TIMEOUT,
+        read_timeout: CHECK_TIMEOUT,
+        retries: 0,
+        ..options.clone()
+    };
+    thread::spawn(move || {
+        let notice = check(&current_version, &options)
+            .ok()
+            .filter(|status| status.update_available)
+            .map(|status| {
+                format!(
+                    "tagcm {} is available (current {}), run `tagcm update` to install it",
+                    status.latest, status.current
+                )
+            });
+        _ = sender.send(notice);
+    });
+    Some(Notice { receiver })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_due() {
+        let stamp = std::env::temp_dir().join(format!("tagcm_stamp_{}", std::process::id()));
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        _ = fs::remove_file(&stamp);
+        assert!(is_due(&stamp, day, now));
+
+        record(&stamp, now);
+        assert!(!is_due(&stamp, day, now));
+        assert!(!is_due(&stamp, day, now + day / 2));
+        assert!(is_due(&stamp, day, now + day));
+
+        fs::write(&stamp, "garbage").unwrap();
+        assert!(is_due(&stamp, day, now));
+        fs::remove_file(&stamp).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Result};
+
+/// Names a release asset may use for each architecture, `std::env::consts::ARCH` first.
+const ARCH_ALIASES: &[(&str, &[&str])] = &[
+    ("x86_64", &["x86_64", "amd64", "x64"]),
+    ("aarch64", &["aarch64", "arm64"]),
+    ("x86", &["i686", "i586", "i386", "x86"]),
+    ("arm", &["armv7", "armhf", "arm"]),
+];
+
+/// Names a release asset may use for each OS, `std::env::consts::OS` first.
+const OS_ALIASES: &[(&str, &[&str])] = &[
+    ("linux", &["linux"]),
+    ("macos", &["darwin", "macos", "apple"]),
+    ("windows", &["windows", "win64", "win32"]),
+];
+
+/// The libc or toolchain part of a target triple.
+const ENVS: &[&str] = &["gnu", "musl", "msvc"];
+
+/// macOS releases may ship one binary for every architecture.
+const UNIVERSAL: &str = "universal";
+
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+/// The target triple a release asset has to be built for to run here.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Platform {
+    pub arch: String,
+    pub os: String,
+    /// `gnu`, `musl`, `msvc`, or empty when the OS has none (macOS).
+    pub env: String,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        let env = if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else if cfg!(target_env = "gnu") {
+            "gnu"
+        } else {
+            ""
+        };
+        Platform {
+            arch: std::env::consts::ARCH.to_string(),
+            os: std::env::consts::OS.to_string(),
+            env: env.to_string(),
+        }
+    }
+
+    /// The name the release workflow gives the archive for this platform.
+    pub fn default_asset_name(&self) -> Result<String> {
+        let triple = match self.os.as_str() {
+            "macos" => format!("{}-apple-darwin", self.arch),
+            "windows" => format!("{}-pc-windows-{}.exe", self.arch, self.env),
+            "linux" => format!("{}-unknown-linux-{}", self.arch, self.env),
+            _ => return Err(anyhow!("unsupported OS: {}", self.os)),
+        };
+        Ok(format!("tagcm-{}{}", triple, ARCHIVE_SUFFIX))
+    }
+
+    // the most preferred first, `None` is an asset without env in its name.
+    // a gnu build also runs the static musl one, a musl host may have no glibc.
+    fn env_preference(&self) -> Vec<Option<&str>> {
+        match (self.os.as_str(), self.env.as_str()) {
+            ("linux", "musl") => vec![Some("musl"), None],
+            ("linux", _) => vec![Some("gnu"), Some("musl"), None],
+            ("windows", "gnu") => vec![Some("gnu"), Some("msvc"), None],
+            ("windows", _) => vec![Some("msvc"), Some("gnu"), None],
+            _ => vec![None],
+        }
+    }
+
+    fn matches(aliases: &[(&str, &[&str])], name: &str, token: &str) -> bool {
+        aliases
+            .iter()
+            .find(|(canonical, _)| *canonical == name)
+            .map_or(token == name, |(_, names)| names.contains(&token))
+    }
+
+    // how well an asset fits, lower is better, `None` when it does not run here.
+    fn rank(&self, asset: &str) -> Option<usize> {
+        let stem = asset.strip_suffix(ARCHIVE_SUFFIX)?;
+        let stem = stem.strip_suffix(".exe").unwrap_or(stem);
+        let tokens: Vec<&str> = stem.split('-').collect();
+
+        let arch = tokens.iter().any(|token| {
+            Self::matches(ARCH_ALIASES, &self.arch, token)
+                || (self.os == "macos" && token.starts_with(UNIVERSAL))
+        });
+        let os = tokens
+            .iter()
+            .any(|token| Self::matches(OS_ALIASES, &self.os, token));
+        if !arch || !os {
+            return None;
+        }
+        let env = tokens.iter().find(|token| ENVS.contains(token)).copied();
+        self.env_preference().iter().position(|e| *e == env)
+    }
+
+    /// Picks the archive built for this platform out of the assets of a release.
+    pub fn select_asset<'a>(&self, assets: &'a [String]) -> Result<&'a str> {
+        assets
+            .iter()
+            .filter_map(|asset| self.rank(asset).map(|rank| (rank, asset)))
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, asset)| asset.as_str())
+            .ok_or_else(|| {
+                let archives: Vec<&str> = assets
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|asset| asset.ends_with(ARCHIVE_SUFFIX))
+                    .collect();
+                anyhow!(
+                    "no release asset for {}, available: {}",
+                    self,
+                    if archives.is_empty() {
+                        "none".to_string()
+                    } else {
+                        archives.join(", ")
+                    }
+                )
+            })
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", self.arch, self.os)?;
+        if !self.env.is_empty() {
+            write!(f, "-{}", self.env)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(arch: &str, os: &str, env: &str) -> Platform {
+        Platform {
+            arch: arch.to_string(),
+            os: os.to_string(),
+            env: env.to_string(),
+        }
+    }
+
+    const ASSETS: &[&str] = &[
+        "tagcm-x86_64-unknown-linux-gnu.tar.gz",
+        "tagcm-x86_64-unknown-linux-gnu.sha256",
+        "tagcm-x86_64-unknown-linux-gnu.tar.gz.sig",
+        "tagcm-x86_64-unknown-linux-musl.tar.gz",
+        "tagcm-aarch64-unknown-linux-musl.tar.gz",
+        "tagcm-x86_64-apple-darwin.tar.gz",
+        "tagcm-aarch64-apple-darwin.tar.gz",
+        "tagcm-x86_64-pc-windows-msvc.exe.tar.gz",
+        "tagcm-i686-pc-windows-msvc.exe.tar.gz",
+    ];
+
+    #[test]
+    fn test_select_asset() {
+        let assets: Vec<String> = ASSETS.iter().map(|a| a.to_string()).collect();
+        let cases = [
+            (
+                platform("x86_64", "linux", "gnu"),
+                "tagcm-x86_64-unknown-linux-gnu.tar.gz",
+            ),
+            (
+                platform("x86_64", "linux", "musl"),
+                "tagcm-x86_64-unknown-linux-musl.tar.gz",
+            ),
+            // no gnu build for aarch64, the static musl one runs as well.
+            (
+                platform("aarch64", "linux", "gnu"),
+                "tagcm-aarch64-unknown-linux-musl.tar.gz",
+            ),
+            (
+                platform("aarch64", "macos", ""),
+                "tagcm-aarch64-apple-darwin.tar.gz",
+            ),
+            (
+                platform("x86_64", "macos", ""),
+                "tagcm-x86_64-apple-darwin.tar.gz",
+            ),
+            (
+                platform("x86_64", "windows", "msvc"),
+                "tagcm-x86_64-pc-windows-msvc.exe.tar.gz",
+            ),
+            (
+                platform("x86", "windows", "msvc"),
+                "tagcm-i686-pc-windows-msvc.exe.tar.gz",
+            ),
+        ];
+        for (platform, expected) in cases {
+            assert_eq!(
+                platform.select_asset(&assets).unwrap(),
+                expected,
+                "{}",
+                platform
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_asset_aliases() {
+        let cases = [
+            (
+                platform("aarch64", "linux", "gnu"),
+                vec![
+                    "tagcm-v1.2.0-linux-amd64.tar.gz",
+                    "tagcm-v1.2.0-linux-arm64.tar.gz",
+                ],
+                "tagcm-v1.2.0-linux-arm64.tar.gz",
+            ),
+            (
+                platform("x86_64", "linux", "gnu"),
+                vec![
+                    "tagcm-v1.2.0-linux-arm64.tar.gz",
+                    "tagcm-v1.2.0-linux-amd64.tar.gz",
+                ],
+                "tagcm-v1.2.0-linux-amd64.tar.gz",
+            ),
+            (
+                platform("aarch64", "macos", ""),
+                vec![
+                    "tagcm-linux-arm64.tar.gz",
+                    "tagcm-1.2.0-darwin-universal.tar.gz",
+                ],
+                "tagcm-1.2.0-darwin-universal.tar.gz",
+            ),
+            // the gnu build is preferred over musl when both exist.
+            (
+                platform("x86_64", "linux", "gnu"),
+                vec![
+                    "tagcm-x86_64-unknown-linux-musl.tar.gz",
+                    "tagcm-x86_64-unknown-linux-gnu.tar.gz",
+                ],
+                "tagcm-x86_64-unknown-linux-gnu.tar.gz",
+            ),
+        ];
+        for (platform, assets, expected) in cases {
+            let assets: Vec<String> = assets.iter().map(|a| a.to_string()).collect();
+            assert_eq!(
+                platform.select_asset(&assets).unwrap(),
+                expected,
+                "{}",
+                platform
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_asset_no_match() {
+        let cases = [
+            (platform("aarch64", "windows", "msvc"), ASSETS.to_vec()),
+            // a musl host may have no glibc to run the gnu build.
+            (
+                platform("x86_64", "linux", "musl"),
+                vec!["tagcm-x86_64-unknown-linux-gnu.tar.gz"],
+            ),
+            (
+                platform("arm", "linux", "gnu"),
+                vec!["tagcm-aarch64-unknown-linux-gnu.tar.gz"],
+            ),
+        ];
+        for (platform, assets) in cases {
+            let assets: Vec<String> = assets.iter().map(|a| a.to_string()).collect();
+            let err = platform.select_asset(&assets).unwrap_err().to_string();
+            assert!(err.starts_with(&format!("no release asset for {}", platform)));
+            assert!(err.contains(&assets[0]), "{}", err);
+        }
+
+        let err = platform("x86_64", "linux", "gnu")
+            .select_asset(&["tagcm.sha256".to_string()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no release asset for x86_64-linux-gnu, available: none"
+        );
+    }
+
+    #[test]
+    fn test_default_asset_name() {
+        let cases = [
+            (
+                platform("x86_64", "linux", "musl"),
+                "tagcm-x86_64-unknown-linux-musl.tar.gz",
+            ),
+            (
+                platform("aarch64", "macos", ""),
+                "tagcm-aarch64-apple-darwin.tar.gz",
+            ),
+            (
+                platform("x86_64", "windows", "msvc"),
+                "tagcm-x86_64-pc-windows-msvc.exe.tar.gz",
+            ),
+        ];
+        for (platform, expected) in cases {
+            assert_eq!(platform.default_asset_name().unwrap(), expected);
+        }
+        assert!(platform("x86_64", "freebsd", "")
+            .default_asset_name()
+            .is_err());
+    }
+}
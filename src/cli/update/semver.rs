@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Error, Result};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Identifier {
+    Numeric(u64),
+    AlphaNumeric(String),
+}
+
+impl Identifier {
+    fn new(identifier: &str) -> Result<Self, Error> {
+        if identifier.is_empty()
+            || !identifier
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(anyhow!("invalid identifier \"{}\"", identifier));
+        }
+        if identifier.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(Identifier::Numeric(parse_number(identifier)?));
+        }
+        Ok(Identifier::AlphaNumeric(identifier.to_string()))
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Identifier::Numeric(n) => write!(f, "{}", n),
+            Identifier::AlphaNumeric(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+// numeric identifiers have lower precedence than alphanumeric ones.
+impl Ord for Identifier {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        match (self, rhs) {
+            (Identifier::Numeric(a), Identifier::Numeric(b)) => a.cmp(b),
+            (Identifier::AlphaNumeric(a), Identifier::AlphaNumeric(b)) => a.cmp(b),
+            (Identifier::Numeric(_), Identifier::AlphaNumeric(_)) => Ordering::Less,
+            (Identifier::AlphaNumeric(_), Identifier::Numeric(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+// no leading zeros, as https://semver.org requires.
+fn parse_number(digits: &str) -> Result<u64, Error> {
+    if digits.len() > 1 && digits.starts_with('0') {
+        return Err(anyhow!("invalid version digits"));
+    }
+    digits
+        .parse::<u64>()
+        .map_err(|_| anyhow!("invalid version digits"))
+}
+
+/// A version as https://semver.org defines it, with an optional leading `v`.
+/// Build metadata is kept for display but ignored when versions are compared.
+#[derive(Clone, Debug)]
+pub struct Semver {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Vec<Identifier>,
+    build: Option<String>,
+}
+
+impl Semver {
+    pub fn new(version: &str) -> Result<Self, Error> {
+        let version = version.trim_start_matches('v');
+
+        let (version, build) = match version.split_once('+') {
+            Some((version, build)) => {
+                for identifier in build.split('.') {
+                    Identifier::new(identifier)?;
+                }
+                (version, Some(build.to_string()))
+            }
+            None => (version, None),
+        };
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (
+                version,
+                pre.split('.')
+                    .map(Identifier::new)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => (version, Vec::new()),
+        };
+
+        let parts: Vec<&str> = version.split('.').collect();
+        if parts.len() != 3 {
+            return Err(anyhow!("invalid version"));
+        }
+
+        Ok(Semver {
+            major: parse_number(parts[0])?,
+            minor: parse_number(parts[1])?,
+            patch: parse_number(parts[2])?,
+            pre,
+            build,
+        })
+    }
+
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+}
+
+impl fmt::Display for Semver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            let pre: Vec<String> = self.pre.iter().map(|i| i.to_string()).collect();
+            write!(f, "-{}", pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Semver {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        self.major
+            .cmp(&rhs.major)
+            .then(self.minor.cmp(&rhs.minor))
+            .then(self.patch.cmp(&rhs.patch))
+            // a pre-release comes before the release it leads to.
+            .then(match (self.pre.is_empty(), rhs.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => self.pre.cmp(&rhs.pre),
+            })
+    }
+}
+
+impl PartialOrd for Semver {
+    fn partial_cmp(&self, rhs: &Self) -> Option<Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+
+impl PartialEq for Semver {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cmp(rhs) == Ordering::Equal
+    }
+}
+
+impl Eq for Semver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_semver_greater() {
+        let v1 = Semver::new("v0.1.0").unwrap();
+        let v2 = Semver::new("v0.1.1").unwrap();
+        let v3 = Semver::new("v0.2.0").unwrap();
+        let v4 = Semver::new("v1.0.0").unwrap();
+
+        assert!(v1 < v2);
+        assert!(v2 < v3);
+        assert!(v3 < v4);
+        assert!(v1 < v4);
+    }
+
+    #[test]
+    fn test_semver_is_same() {
+        let v1 = Semver::new("v0.1.0").unwrap();
+        let v2 = Semver::new("v0.1.0").unwrap();
+        assert!(v1 == v2);
+
+        let v3 = Semver::new("v0.1.1").unwrap();
+        assert!(v1 != v3);
+
+        let v4 = Semver::new("v0.1.0+build.5").unwrap();
+        assert!(v1 == v4);
+    }
+
+    #[test]
+    fn test_semver_invalid() {
+        let v1 = Semver::new("v0.1").unwrap_err();
+        let v2 = Semver::new("v0.1.0.0").unwrap_err();
+        let v3 = Semver::new("v0.").unwrap_err();
+        let v4 = Semver::new("va.b.c").unwrap_err();
+
+        assert_eq!(v1.to_string(), "invalid version");
+        assert_eq!(v2.to_string(), "invalid version");
+        assert_eq!(v3.to_string(), "invalid version");
+
+        assert_eq!(v4.to_string(), "invalid version digits");
+
+        assert!(Semver::new("v01.0.0").is_err());
+        assert!(Semver::new("v1.0.0-").is_err());
+        assert!(Semver::new("v1.0.0-beta..1").is_err());
+        assert!(Semver::new("v1.0.0-beta.01").is_err());
+        assert!(Semver::new("v1.0.0+").is_err());
+        assert!(Semver::new("v1.0.0-beta_1").is_err());
+    }
+
+    #[test]
+    fn test_semver_prerelease_order() {
+        // the example from https://semver.org/#spec-item-11
+        let versions: Vec<Semver> = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ]
+        .iter()
+        .map(|v| Semver::new(v).unwrap())
+        .collect();
+        for pair in versions.windows(2) {
+            assert!(pair[0] < pair[1], "{} < {}", pair[0], pair[1]);
+        }
+        assert!(versions[0].is_prerelease());
+        assert!(!versions[7].is_prerelease());
+        assert!(Semver::new("v0.9.9").unwrap() < versions[0]);
+    }
+
+    #[test]
+    fn test_semver_display() {
+        assert_eq!(
+            Semver::new("1.2.3-rc.1+build.7").unwrap().to_string(),
+            "v1.2.3-rc.1+build.7"
+        );
+        assert_eq!(Semver::new("v1.2.3").unwrap().to_string(), "v1.2.3");
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+/// Hex encoded ed25519 key the release assets are signed with, set when the release binary is built.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("TAGCM_RELEASE_PUBLIC_KEY");
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err(anyhow!("invalid hex"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| anyhow!("invalid hex")))
+        .collect()
+}
+
+fn is_sha256_hex(text: &str) -> bool {
+    text.len() == 64 && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Finds the digest in a checksum file, either `<hex>  <file name>` as written by `shasum`
+/// or the bare hex, possibly split by spaces, as written by `certutil`.
+pub fn parse_checksum(checksum_file: &str) -> Result<String> {
+    for line in checksum_file.lines() {
+        if let Some(first) = line.split_whitespace().next().filter(|t| is_sha256_hex(t)) {
+            return Ok(first.to_lowercase());
+        }
+        let joined: String = line.split_whitespace().collect();
+        if is_sha256_hex(&joined) {
+            return Ok(joined.to_lowercase());
+        }
+    }
+    Err(anyhow!("no sha256 checksum found"))
+}
+
+pub fn verify_checksum(data: &[u8], checksum_file: &str) -> Result<()> {
+    let expected = parse_checksum(checksum_file)?;
+    let actual = sha256_hex(data);
+    if expected != actual {
+        return Err(anyhow!(
+            "checksum mismatch, expected {} but got {}",
+            expected,
+            actual
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a hex encoded ed25519 signature of `data` against the hex encoded public key.
+pub fn verify_signature(data: &[u8], signature_file: &str, public_key: &str) -> Result<()> {
+    let key: [u8; 32] = decode_hex(public_key.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("invalid public key length"))?;
+    let key = VerifyingKey::from_bytes(&key)?;
+    let signature: [u8; 64] = decode_hex(signature_file.trim())?
+        .try_into()
+        .map_err(|_| anyhow!("invalid signature length"))?;
+    key.verify_strict(data, &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow!("signature verification failed"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const ARCHIVE: &[u8] = b"tagcm release archive";
+
+    fn encode_hex(data: &[u8]) -> String {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_verify_checksum() {
+        let digest = sha256_hex(ARCHIVE);
+        let shasum = format!("{}  tagcm-x86_64-unknown-linux-gnu.tar.gz\n", digest);
+        verify_checksum(ARCHIVE, &shasum).unwrap();
+        verify_checksum(ARCHIVE, &digest.to_uppercase()).unwrap();
+
+        let certutil: Vec<String> = digest
+            .as_bytes()
+            .chunks(2)
+            .map(|c| String::from_utf8(c.to_vec()).unwrap())
+            .collect();
+        verify_checksum(ARCHIVE, &certutil.join(" ")).unwrap();
+
+        let err = verify_checksum(b"tampered", &shasum).unwrap_err();
+        assert!(err.to_string().starts_with("checksum mismatch"));
+        assert!(verify_checksum(ARCHIVE, "<html>not found</html>").is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let key = signing_key(7);
+        let public_key = encode_hex(key.verifying_key().as_bytes());
+        let signature = encode_hex(&key.sign(ARCHIVE).to_bytes());
+
+        verify_signature(ARCHIVE, &format!("{}\n", signature), &public_key).unwrap();
+        assert!(verify_signature(b"tampered", &signature, &public_key).is_err());
+
+        let other_key = encode_hex(signing_key(8).verifying_key().as_bytes());
+        assert!(verify_signature(ARCHIVE, &signature, &other_key).is_err());
+        assert!(verify_signature(ARCHIVE, "abcd", &public_key).is_err());
+        assert!(verify_signature(ARCHIVE, &signature, "zz").is_err());
+    }
+}
@@ -1,7 +1,16 @@
-pub mod add;
-pub mod delete;
+pub mod risk;
 pub mod search;
-pub mod show;
-pub mod update;
+pub mod secret;
+pub mod tag;
+
+use serde::{Deserialize, Serialize};
 
 pub const ALL_SUBCOMMAND: &str = "all";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
@@ -0,0 +1,183 @@
+use super::search::TagData;
+use crate::config::RiskConfig;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+/// What the built-in rules look for, a name for the warning and a pattern.
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    (
+        "recursive rm",
+        r"\brm\s+(?:-\S+\s+)*(?:-[a-zA-Z]*[rR]|--recursive\b)",
+    ),
+    ("kubectl delete", r"\bkubectl\s+(?:\S+\s+)*?delete\b"),
+    (
+        "force push",
+        r"\bgit\s+push\b.*(?:\s--force(?:-with-lease)?\b|\s-[a-zA-Z]*f\b)",
+    ),
+    ("git reset --hard", r"\bgit\s+reset\s+(?:\S+\s+)*?--hard\b"),
+    ("git clean", r"\bgit\s+clean\s+(?:\S+\s+)*?-[a-zA-Z]*f"),
+    (
+        "SQL drop or truncate",
+        r"(?i)\b(?:drop\s+(?:table|database|schema)|truncate\s+table)\b",
+    ),
+    (
+        "raw disk write",
+        r"\bdd\b.*\bof=/dev/|>\s*/dev/(?:sd|nvme|disk|hd)",
+    ),
+    ("mkfs", r"\bmkfs(?:\.\w+)?\b"),
+    ("terraform destroy", r"\bterraform\s+(?:\S+\s+)*?destroy\b"),
+    ("shutdown", r"\b(?:shutdown|reboot|poweroff|halt)\b"),
+    (
+        "pipe to shell",
+        r"\b(?:curl|wget)\b[^|]*\|\s*(?:sudo\s+)?(?:ba|z)?sh\b",
+    ),
+    ("chmod 777", r"\bchmod\s+(?:-\S+\s+)*0?777\b"),
+];
+
+/// Patterns of commands that ask for confirmation before they run or are copied.
+#[derive(Clone, Debug)]
+pub struct RiskRules {
+    rules: Vec<(String, Regex)>,
+}
+
+impl Default for RiskRules {
+    fn default() -> Self {
+        RiskRules::new(&RiskConfig::default()).unwrap()
+    }
+}
+
+impl RiskRules {
+    /// The built-in rules, unless turned off, and the patterns of `config`.
+    pub fn new(config: &RiskConfig) -> Result<Self> {
+        let builtin = BUILTIN_RULES
+            .iter()
+            .filter(|_| config.builtin)
+            .map(|(name, pattern)| (name.to_string(), pattern.to_string()));
+        let custom = config
+            .patterns
+            .iter()
+            .map(|pattern| (pattern.clone(), pattern.clone()));
+        let rules = builtin
+            .chain(custom)
+            .map(|(name, pattern)| {
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| anyhow!("invalid risk pattern {}: {}", pattern, e))?;
+                Ok((name, regex))
+            })
+            .collect::<Result<_>>()?;
+        Ok(RiskRules { rules })
+    }
+
+    /// Names of the rules `command` matches, empty when it looks harmless.
+    pub fn check(&self, command: &str) -> Vec<String> {
+        self.rules
+            .iter()
+            .filter(|(_, regex)| regex.is_match(command))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Fills in the `risks` of every entry.
+    pub fn mark(&self, entries: &mut [TagData]) {
+        for entry in entries {
+            entry.risks = self.check(&entry.command);
+        }
+    }
+
+    /// The warning to print when `command` is stored under `tag`, if it looks dangerous.
+    pub fn warning(&self, tag: &str, command: &str) -> Option<String> {
+        let risks = self.check(command);
+        if risks.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "warning: {} looks dangerous ({}), it will ask before it runs or is copied",
+            tag,
+            risks.join(", ")
+        ))
+    }
+}
+
+/// The risky ones of `entries`, each as its tag and what makes it risky.
+pub fn risky(entries: &[TagData]) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|entry| !entry.risks.is_empty())
+        .map(|entry| format!("{} ({})", entry.tag, entry.risks.join(", ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_rules() {
+        let rules = RiskRules::default();
+        for (command, rule) in [
+            ("rm -rf /tmp/x", "recursive rm"),
+            ("rm -fr build", "recursive rm"),
+            ("sudo rm -f -r ~/x", "recursive rm"),
+            ("kubectl -n prod delete ns web", "kubectl delete"),
+            ("git push -f origin main", "force push"),
+            ("git push --force-with-lease", "force push"),
+            ("git reset HEAD~1 --hard", "git reset --hard"),
+            ("git clean -xfd", "git clean"),
+            ("psql -c 'DROP TABLE users'", "SQL drop or truncate"),
+            ("dd if=x.iso of=/dev/sdb", "raw disk write"),
+            ("mkfs.ext4 /dev/sdb1", "mkfs"),
+            ("terraform -chdir=prod destroy", "terraform destroy"),
+            ("sudo systemctl reboot", "shutdown"),
+            ("curl -fsSL https://x.sh | sudo bash", "pipe to shell"),
+            ("chmod -R 777 /srv", "chmod 777"),
+        ] {
+            assert_eq!(rules.check(command), vec![rule], "{}", command);
+        }
+        for command in [
+            "rm -f build.log",
+            "kubectl get pods",
+            "git push --follow-tags",
+            "git reset --soft HEAD~1",
+            "curl -o x.sh https://x.sh",
+            "echo halting",
+            "ls -la",
+        ] {
+            assert!(rules.check(command).is_empty(), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let config = RiskConfig {
+            builtin: false,
+            patterns: vec![r"\bhelm\s+uninstall\b".to_string()],
+        };
+        let rules = RiskRules::new(&config).unwrap();
+        assert_eq!(
+            rules.check("helm uninstall web"),
+            vec![r"\bhelm\s+uninstall\b"]
+        );
+        assert!(rules.check("rm -rf /").is_empty());
+        assert!(rules.warning("ok", "ls").is_none());
+        assert!(rules
+            .warning("web", "helm uninstall web")
+            .unwrap()
+            .starts_with("warning: web looks dangerous"));
+
+        let config = RiskConfig {
+            patterns: vec!["(".to_string()],
+            ..RiskConfig::default()
+        };
+        assert!(RiskRules::new(&config).is_err());
+    }
+
+    #[test]
+    fn test_risky() {
+        let mut entries = vec![TagData::new("ls".to_string(), "ls".to_string())];
+        RiskRules::default().mark(&mut entries);
+        assert!(risky(&entries).is_empty());
+        entries.push(TagData::new("wipe".to_string(), "rm -rf /".to_string()));
+        RiskRules::default().mark(&mut entries);
+        assert_eq!(risky(&entries), vec!["wipe (recursive rm)"]);
+    }
+}
@@ -1,47 +1,179 @@
+pub mod keymap;
+
+use super::risk::RiskRules;
+use super::secret::SecretRules;
+use super::tag::NAMESPACE_SEPARATOR;
+use super::OutputFormat;
 use crate::repo::tag_data_repository::TagDataRepository;
 use anyhow::Result;
+use keymap::{Action, KeyMap};
+use serde::{Deserialize, Serialize};
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEventKind},
-    terminal::{enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    cursor,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 
-use cli_clipboard;
-use ratatui::{prelude::*, widgets::*};
+use ratatui::{prelude::*, widgets::*, TerminalOptions, Viewport};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    SigId,
+};
+use std::collections::HashSet;
+use std::fmt;
 use std::io;
 use std::io::stdout;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering as AtomicOrdering};
+use std::sync::{Arc, Once};
+use std::time::Duration;
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+/// A tag and its command, as `search` and `show` return them.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct TagData {
     pub tag: String,
     pub command: String,
+    /// The layer the entry comes from when several stores are stacked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// The rules the command breaks, filled in by `RiskRules::mark`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub risks: Vec<String>,
 }
 
 impl TagData {
-    fn new(tag: String, command: String) -> TagData {
-        TagData { tag, command }
+    pub fn new(tag: String, command: String) -> TagData {
+        TagData {
+            tag,
+            command,
+            origin: None,
+            risks: Vec::new(),
+        }
     }
-}
 
-pub fn search<T: TagDataRepository>(repo: &T, search_str: String) -> Result<Vec<TagData>> {
-    let all_tags = repo.get_all_tags();
+    fn risk_note(&self) -> String {
+        if self.risks.is_empty() {
+            return String::new();
+        }
+        format!(" [dangerous: {}]", self.risks.join(", "))
+    }
 
-    let mut results: Vec<TagData> = Vec::new();
-    for tag in all_tags {
-        if tag.starts_with(&search_str) {
-            match repo.get_tag_data(&tag) {
-                Some(cmd) => {
-                    results.push(TagData::new(tag, cmd));
-                }
-                None => println!("Command not found"),
+    /// The entry on one line, a multi-line command is cut after its first line.
+    pub fn summary(&self) -> String {
+        let mut lines = self.command.lines();
+        let mut text = format!("{}: {}", self.tag, lines.next().unwrap_or_default());
+        let more = lines.count();
+        if more > 0 {
+            text.push_str(&format!(" … (+{} lines)", more));
+        }
+        if let Some(origin) = &self.origin {
+            text.push_str(&format!(" ({})", origin));
+        }
+        text + &self.risk_note()
+    }
+
+    pub fn from_repo<T: TagDataRepository + ?Sized>(repo: &T, tag: String) -> Option<TagData> {
+        let command = repo.get_tag_data(&tag)?;
+        let origin = repo.get_origin(&tag);
+        Some(TagData {
+            origin,
+            ..TagData::new(tag, command)
+        })
+    }
+}
+
+/// `tag: command`, a multi-line command goes on the lines below, indented.
+impl fmt::Display for TagData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let multi_line = self.command.contains('\n');
+        write!(f, "{}:", self.tag)?;
+        if !multi_line {
+            write!(f, " {}", self.command)?;
+        }
+        if let Some(origin) = &self.origin {
+            write!(f, " ({})", origin)?;
+        }
+        write!(f, "{}", self.risk_note())?;
+        if multi_line {
+            for line in self.command.lines() {
+                write!(f, "\n    {}", line)?;
             }
         }
+        Ok(())
     }
+}
+
+/// The entries whose tag starts with `search_str`, sorted by tag.
+pub fn search<T: TagDataRepository + ?Sized>(repo: &T, search_str: &str) -> Result<Vec<TagData>> {
+    let mut results: Vec<TagData> = repo
+        .get_all_tags()
+        .into_iter()
+        .filter(|tag| tag.starts_with(search_str))
+        .filter_map(|tag| TagData::from_repo(repo, tag))
+        .collect();
     results.sort();
     Ok(results)
 }
 
+/// The entries of `namespace`, or of every one without it, whose tag within it starts
+/// with `search_str`, sorted by tag.
+pub fn search_in<T: TagDataRepository + ?Sized>(
+    repo: &T,
+    namespace: Option<&str>,
+    search_str: &str,
+) -> Result<Vec<TagData>> {
+    let prefix = match namespace {
+        Some(namespace) => super::tag::namespace_prefix(namespace)? + search_str,
+        None => search_str.to_string(),
+    };
+    search(repo, &prefix)
+}
+
+/// Renders search `results` one per line with their origin and risks, or as a JSON array.
+pub fn format_results(results: &[TagData], format: OutputFormat) -> Result<String> {
+    if format == OutputFormat::Json {
+        return Ok(format!("{}\n", serde_json::to_string_pretty(results)?));
+    }
+    Ok(results
+        .iter()
+        .map(|data| {
+            let risks = match data.risks.is_empty() {
+                true => String::new(),
+                false => format!(", dangerous: {}", data.risks.join(", ")),
+            };
+            match &data.origin {
+                Some(origin) => format!(
+                    "tag: {}, command: {}, origin: {}{}\n",
+                    data.tag, data.command, origin, risks
+                ),
+                None => format!("tag: {}, command: {}{}\n", data.tag, data.command, risks),
+            }
+        })
+        .collect())
+}
+
+/// Order of search results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+    #[default]
+    Tag,
+    TagDesc,
+    Command,
+}
+
+impl Sort {
+    pub fn apply(&self, results: &mut [TagData]) {
+        match self {
+            Sort::Tag => results.sort(),
+            Sort::TagDesc => results.sort_by(|a, b| b.cmp(a)),
+            Sort::Command => results.sort_by(|a, b| a.command.cmp(&b.command)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,53 +182,558 @@ mod tests {
     #[test]
     fn test_search_single_tag() {
         let mut repo = UnitTestRepository::new();
-        repo.add_tag_data("test".to_string(), "echo test".to_string());
-        let result = search(&repo, "test".to_string()).unwrap();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        let result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].tag, "test");
         assert_eq!(result[0].command, "echo test");
 
-        repo.remove_tag_data("test");
-        let result = search(&repo, "test".to_string()).unwrap();
+        repo.remove_tag_data("test").unwrap();
+        let result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 0);
     }
 
     #[test]
     fn test_search_different_prefix() {
         let mut repo = UnitTestRepository::new();
-        repo.add_tag_data("test".to_string(), "echo test".to_string());
-        repo.add_tag_data("hoge".to_string(), "echo hoge".to_string());
-        let result = search(&repo, "test".to_string()).unwrap();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        repo.add_tag_data("hoge".to_string(), "echo hoge".to_string())
+            .unwrap();
+        let result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].tag, "test");
         assert_eq!(result[0].command, "echo test");
 
-        repo.remove_tag_data("test");
-        repo.remove_tag_data("hoge");
-        let result = search(&repo, "test".to_string()).unwrap();
+        repo.remove_tag_data("test").unwrap();
+        repo.remove_tag_data("hoge").unwrap();
+        let result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 0);
     }
 
     #[test]
     fn test_search_same_prefix() {
         let mut repo = UnitTestRepository::new();
-        repo.add_tag_data("test".to_string(), "echo test".to_string());
-        repo.add_tag_data("test2".to_string(), "echo test2".to_string());
-        let result = search(&repo, "test".to_string()).unwrap();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        repo.add_tag_data("test2".to_string(), "echo test2".to_string())
+            .unwrap();
+        let result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].tag, "test");
         assert_eq!(result[0].command, "echo test");
         assert_eq!(result[1].tag, "test2");
         assert_eq!(result[1].command, "echo test2");
 
-        repo.remove_tag_data("test");
-        let mut result = search(&repo, "test".to_string()).unwrap();
+        repo.remove_tag_data("test").unwrap();
+        let mut result = search(&repo, "test").unwrap();
         assert_eq!(result.len(), 1);
 
-        repo.remove_tag_data("hoge");
-        result = search(&repo, "hoge".to_string()).unwrap();
+        assert!(repo.remove_tag_data("hoge").is_err());
+        result = search(&repo, "hoge").unwrap();
         assert_eq!(result.len(), 0);
     }
+
+    #[test]
+    fn test_multi_select_keeps_mark_order() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("deploy".to_string(), "make deploy".to_string())
+            .unwrap();
+        repo.add_tag_data("login".to_string(), "aws sso login".to_string())
+            .unwrap();
+        repo.add_tag_data("port".to_string(), "kubectl port-forward".to_string())
+            .unwrap();
+
+        let mut app = App::default();
+        app.auto_complete(&repo);
+        app.add_current_command_input(-1);
+        app.toggle_mark();
+        app.add_current_command_input(-1);
+        app.toggle_mark();
+        app.add_current_command_input(-1);
+        app.toggle_mark();
+        app.toggle_mark();
+        assert!(app.is_marked("port"));
+        assert!(!app.is_marked("deploy"));
+
+        let targets = app.take_targets();
+        let commands: Vec<String> = targets.into_iter().map(|t| t.command).collect();
+        assert_eq!(
+            commands.join(Join::And.separator()),
+            "kubectl port-forward && aws sso login"
+        );
+        assert!(app.marked.is_empty());
+    }
+
+    struct ScriptedEvents(std::collections::VecDeque<Event>);
+
+    impl ScriptedEvents {
+        fn new(events: Vec<Event>) -> Self {
+            ScriptedEvents(events.into())
+        }
+    }
+
+    impl EventSource for ScriptedEvents {
+        fn next_event(&mut self) -> io::Result<Option<Event>> {
+            Ok(self.0.pop_front())
+        }
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(event::KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn ctrl(c: char) -> Event {
+        Event::Key(event::KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::CONTROL,
+        ))
+    }
+
+    fn test_repo() -> UnitTestRepository {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("deploy".to_string(), "make deploy".to_string())
+            .unwrap();
+        repo.add_tag_data("login".to_string(), "aws sso login".to_string())
+            .unwrap();
+        repo
+    }
+
+    fn run_scripted(events: Vec<Event>) -> (Outcome, Terminal<backend::TestBackend>) {
+        let repo = test_repo();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 20)).unwrap();
+        let mut events = ScriptedEvents::new(events);
+        let outcome = run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        (outcome, terminal)
+    }
+
+    fn buffer_text(terminal: &Terminal<backend::TestBackend>) -> String {
+        let buffer = terminal.backend().buffer();
+        buffer.content.iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn test_run_app_select_by_input() {
+        let (outcome, _) = run_scripted(vec![key(KeyCode::Char('l')), key(KeyCode::Enter)]);
+        assert_eq!(
+            outcome,
+            Outcome::Select(vec![TagData::new(
+                "login".to_string(),
+                "aws sso login".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    fn test_run_app_marks_and_renders() {
+        let (outcome, terminal) = run_scripted(vec![key(KeyCode::Tab), key(KeyCode::Tab)]);
+        assert_eq!(outcome, Outcome::Cancel);
+        assert!(buffer_text(&terminal).contains("Search results (2 marked)"));
+        assert!(buffer_text(&terminal).contains("* deploy: make deploy"));
+    }
+
+    #[test]
+    fn test_run_app_cancel() {
+        let (outcome, _) = run_scripted(vec![key(KeyCode::Esc)]);
+        assert_eq!(outcome, Outcome::Cancel);
+        let (outcome, _) = run_scripted(vec![ctrl('c'), key(KeyCode::Enter)]);
+        assert_eq!(outcome, Outcome::Cancel);
+    }
+
+    #[test]
+    fn test_run_app_delete_marked() {
+        let (outcome, _) = run_scripted(vec![key(KeyCode::Down), key(KeyCode::Tab), ctrl('d')]);
+        assert_eq!(outcome, Outcome::Delete(vec!["login".to_string()]));
+    }
+
+    #[test]
+    fn test_run_app_small_viewport_hides_help() {
+        let repo = test_repo();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 6)).unwrap();
+        let mut events = ScriptedEvents::new(vec![]);
+        run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        let text = buffer_text(&terminal);
+        assert!(!text.contains("Press any key"));
+        assert!(text.contains("deploy: make deploy"));
+
+        clear_inline_viewport(&mut terminal).unwrap();
+        assert!(buffer_text(&terminal).trim().is_empty());
+    }
+
+    #[test]
+    fn test_run_app_resize() {
+        let repo = test_repo();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 20)).unwrap();
+        let mut events = ScriptedEvents::new(vec![Event::Resize(40, 12)]);
+        terminal.backend_mut().resize(40, 12);
+        let outcome = run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        assert_eq!(outcome, Outcome::Cancel);
+        assert_eq!(terminal.size().unwrap(), Rect::new(0, 0, 40, 12));
+    }
+
+    fn tree_repo() -> UnitTestRepository {
+        let mut repo = test_repo();
+        for (tag, command) in [
+            ("k8s/logs/tail", "kubectl logs -f"),
+            ("k8s/get", "kubectl get pods"),
+            ("k8s/logs/all", "kubectl logs"),
+        ] {
+            repo.add_tag_data(tag.to_string(), command.to_string())
+                .unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn test_tree_rows() {
+        let mut app = App::default();
+        app.auto_complete(&tree_repo());
+        let entry = |tag: &str, depth| Row::Entry {
+            index: app.suggestions.iter().position(|d| d.tag == tag).unwrap(),
+            depth,
+        };
+        let namespace = |path: &str, depth, entries| Row::Namespace {
+            path: path.to_string(),
+            depth,
+            entries,
+        };
+        assert_eq!(
+            app.rows,
+            vec![
+                entry("deploy", 0),
+                namespace("k8s/", 0, 3),
+                entry("k8s/get", 1),
+                namespace("k8s/logs/", 1, 2),
+                entry("k8s/logs/all", 2),
+                entry("k8s/logs/tail", 2),
+                entry("login", 0),
+            ]
+        );
+
+        // a namespace marks all of its entries, and unmarks them again.
+        app.add_current_command_input(1);
+        app.toggle_mark();
+        assert_eq!(app.marked.len(), 3);
+        app.toggle_mark();
+        assert!(app.marked.is_empty());
+
+        assert!(app.toggle_collapse());
+        assert_eq!(app.rows.len(), 3);
+        assert_eq!(app.rows[1], namespace("k8s/", 0, 3));
+        assert!(app.take_targets().is_empty());
+        app.add_current_command_input(1);
+        assert!(!app.toggle_collapse());
+    }
+
+    #[test]
+    fn test_run_app_tree() {
+        let repo = tree_repo();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 24)).unwrap();
+        let mut events = ScriptedEvents::new(vec![
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Down),
+            key(KeyCode::Enter),
+            key(KeyCode::Down),
+            key(KeyCode::Enter),
+        ]);
+        let outcome = run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::Select(vec![TagData::new(
+                "login".to_string(),
+                "aws sso login".to_string()
+            )])
+        );
+        let text = buffer_text(&terminal);
+        assert!(text.contains("▾ k8s/ (3)"));
+        assert!(text.contains("    get: kubectl get pods"));
+        assert!(text.contains("  ▸ logs/ (2)"));
+        assert!(!text.contains("tail: kubectl logs -f"));
+    }
+
+    #[test]
+    fn test_namespace_option() {
+        let options = SearchOptions {
+            namespace: "k8s/logs/".to_string(),
+            ..Default::default()
+        };
+        let mut app = App::new(&options);
+        app.input = "t".to_string();
+        app.auto_complete(&tree_repo());
+        let tags: Vec<&str> = app.suggestions.iter().map(|d| d.tag.as_str()).collect();
+        assert_eq!(tags, vec!["k8s/logs/tail"]);
+    }
+
+    #[test]
+    fn test_multi_line_command() {
+        let data = TagData {
+            origin: Some("team".to_string()),
+            ..TagData::new("ci".to_string(), "set -e\nmake\nmake test".to_string())
+        };
+        assert_eq!(
+            data.to_string(),
+            "ci: (team)\n    set -e\n    make\n    make test"
+        );
+        assert_eq!(data.summary(), "ci: set -e … (+2 lines) (team)");
+
+        let mut repo = test_repo();
+        repo.add_tag_data("ci".to_string(), "set -e\nmake\nmake test".to_string())
+            .unwrap();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 24)).unwrap();
+        let mut events = ScriptedEvents::new(vec![key(KeyCode::Char('c'))]);
+        run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        let text = buffer_text(&terminal);
+        assert!(text.contains("ci: set -e … (+2 lines)"));
+        assert!(text.contains("Command"));
+        assert!(text.contains("make test"));
+    }
+
+    #[test]
+    fn test_risky_entries() {
+        let mut repo = test_repo();
+        repo.add_tag_data("wipe".to_string(), "rm -rf build".to_string())
+            .unwrap();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 20)).unwrap();
+        let mut events = ScriptedEvents::new(vec![key(KeyCode::Char('w'))]);
+        run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        let buffer = terminal.backend().buffer();
+        let text = buffer_text(&terminal);
+        assert!(text.contains("! wipe: rm -rf build [dangerous: recursive rm]"));
+        // cells hold one char each, borders take more than a byte.
+        let cell = text[..text.find("! wipe").unwrap()].chars().count();
+        assert_eq!(buffer.content[cell].fg, Color::Red);
+
+        let mut events = ScriptedEvents::new(vec![key(KeyCode::Char('w')), key(KeyCode::Enter)]);
+        let outcome = run_app(&mut terminal, App::default(), &repo, &mut events).unwrap();
+        assert!(matches!(
+            outcome,
+            Outcome::Select(targets) if targets[0].risks == vec!["recursive rm"]
+        ));
+    }
+
+    #[test]
+    fn test_masked_secrets() {
+        let mut repo = test_repo();
+        repo.add_tag_data("api".to_string(), "TOKEN=s3cr3t curl x".to_string())
+            .unwrap();
+        let mut terminal = Terminal::new(backend::TestBackend::new(80, 20)).unwrap();
+        let events = || ScriptedEvents::new(vec![key(KeyCode::Char('a')), key(KeyCode::Enter)]);
+        let outcome = run_app(&mut terminal, App::default(), &repo, &mut events()).unwrap();
+        assert!(buffer_text(&terminal).contains("api: TOKEN=******** curl x"));
+        // what is chosen is the command itself.
+        assert!(matches!(
+            outcome,
+            Outcome::Select(targets) if targets[0].command == "TOKEN=s3cr3t curl x"
+        ));
+
+        let app = App {
+            reveal: true,
+            ..App::default()
+        };
+        run_app(&mut terminal, app, &repo, &mut events()).unwrap();
+        assert!(buffer_text(&terminal).contains("api: TOKEN=s3cr3t curl x"));
+    }
+
+    #[test]
+    fn test_exported_masks_secrets() {
+        let targets = || {
+            vec![TagData::new(
+                "api".to_string(),
+                "TOKEN=s3cr3t curl x".to_string(),
+            )]
+        };
+        let text = exported(targets(), &SearchOptions::default()).unwrap();
+        assert!(!text.contains("s3cr3t"));
+        assert!(text.contains("\"api\": \"TOKEN=******** curl x\""));
+        let options = SearchOptions {
+            reveal: true,
+            ..Default::default()
+        };
+        assert!(exported(targets(), &options).unwrap().contains("s3cr3t"));
+    }
+
+    #[test]
+    fn test_search_in_and_format_results() {
+        let mut repo = tree_repo();
+        repo.add_tag_data("tail".to_string(), "tail -f".to_string())
+            .unwrap();
+        let results = search_in(&repo, Some("k8s/logs"), "t").unwrap();
+        let tags: Vec<&str> = results.iter().map(|d| d.tag.as_str()).collect();
+        assert_eq!(tags, vec!["k8s/logs/tail"]);
+        assert_eq!(search_in(&repo, None, "t").unwrap().len(), 1);
+
+        let results = vec![
+            TagData {
+                origin: Some("team".to_string()),
+                risks: vec!["recursive rm".to_string()],
+                ..TagData::new("wipe".to_string(), "rm -rf x".to_string())
+            },
+            TagData::new("ls".to_string(), "ls".to_string()),
+        ];
+        assert_eq!(
+            format_results(&results, OutputFormat::Text).unwrap(),
+            "tag: wipe, command: rm -rf x, origin: team, dangerous: recursive rm\ntag: ls, command: ls\n"
+        );
+        assert!(format_results(&results, OutputFormat::Json)
+            .unwrap()
+            .ends_with("]\n"));
+    }
+
+    #[test]
+    fn test_take_targets_without_marks() {
+        let mut repo = UnitTestRepository::new();
+        repo.add_tag_data("test".to_string(), "echo test".to_string())
+            .unwrap();
+        let mut app = App::default();
+        assert!(app.take_targets().is_empty());
+
+        app.auto_complete(&repo);
+        let targets = app.take_targets();
+        assert_eq!(
+            targets,
+            vec![TagData::new("test".to_string(), "echo test".to_string())]
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Join {
+    #[default]
+    Newline,
+    And,
+}
+
+impl Join {
+    fn separator(&self) -> &'static str {
+        match self {
+            Join::Newline => "\n",
+            Join::And => " && ",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Destination {
+    #[default]
+    Clipboard,
+    Stdout,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub highlight: Color,
+    pub marked: Color,
+    pub danger: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            highlight: Color::White,
+            marked: Color::Yellow,
+            danger: Color::Red,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchOptions {
+    pub join: Join,
+    pub destination: Destination,
+    /// Draw the picker in this many lines under the prompt instead of on the alternate screen.
+    pub inline_height: Option<u16>,
+    pub sort: Sort,
+    pub theme: Theme,
+    pub keymap: KeyMap,
+    /// Only search the tags starting with this namespace prefix, e.g. `k8s/`.
+    pub namespace: String,
+    pub risk: RiskRules,
+    pub secrets: SecretRules,
+    /// Show the secrets in the commands instead of masking them.
+    pub reveal: bool,
+}
+
+/// What the interactive search ended with.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Cancel,
+    /// Entries picked to copy or print.
+    Select(Vec<TagData>),
+    /// Tags marked to delete.
+    Delete(Vec<String>),
+    /// Entries marked to export.
+    Export(Vec<TagData>),
+}
+
+/// A line of the search results: a namespace, which can be collapsed, or an entry.
+#[derive(Clone, Debug, PartialEq)]
+enum Row {
+    Namespace {
+        path: String,
+        depth: usize,
+        entries: usize,
+    },
+    Entry {
+        index: usize,
+        depth: usize,
+    },
+}
+
+/// An index into the suggestions and what is left of its tag below the current namespace.
+type TreeItem<'a> = (usize, &'a str);
+
+// the suggestions as a tree of namespaces, each node where its first entry is.
+fn tree_rows(suggestions: &[TagData], collapsed: &HashSet<String>) -> Vec<Row> {
+    let items: Vec<TreeItem> = suggestions
+        .iter()
+        .enumerate()
+        .map(|(index, data)| (index, data.tag.as_str()))
+        .collect();
+    let mut rows = Vec::new();
+    push_rows(&items, "", 0, collapsed, &mut rows);
+    rows
+}
+
+fn push_rows(
+    items: &[TreeItem],
+    prefix: &str,
+    depth: usize,
+    collapsed: &HashSet<String>,
+    rows: &mut Vec<Row>,
+) {
+    // (namespace, entries under it with the namespace cut off), None for the entries here.
+    let mut nodes: Vec<(Option<&str>, Vec<TreeItem>)> = Vec::new();
+    for &(index, rest) in items {
+        match rest.split_once(NAMESPACE_SEPARATOR) {
+            None => nodes.push((None, vec![(index, rest)])),
+            Some((name, tail)) => match nodes.iter_mut().find(|(n, _)| *n == Some(name)) {
+                Some((_, members)) => members.push((index, tail)),
+                None => nodes.push((Some(name), vec![(index, tail)])),
+            },
+        }
+    }
+    for (name, members) in nodes {
+        let Some(name) = name else {
+            rows.push(Row::Entry {
+                index: members[0].0,
+                depth,
+            });
+            continue;
+        };
+        let path = format!("{}{}{}", prefix, name, NAMESPACE_SEPARATOR);
+        rows.push(Row::Namespace {
+            path: path.clone(),
+            depth,
+            entries: members.len(),
+        });
+        if !collapsed.contains(&path) {
+            push_rows(&members, &path, depth + 1, collapsed, rows);
+        }
+    }
 }
 
 #[derive(Default)]
@@ -105,9 +742,42 @@ struct App {
     cursor_input_position: usize,
     cursor_commnad_position: usize,
     suggestions: Vec<TagData>,
+    /// The suggestions as they are shown, `cursor_commnad_position` points into it.
+    rows: Vec<Row>,
+    collapsed: HashSet<String>,
+    marked: Vec<TagData>,
+    namespace: String,
+    sort: Sort,
+    theme: Theme,
+    keymap: KeyMap,
+    risk: RiskRules,
+    secrets: SecretRules,
+    reveal: bool,
 }
 
 impl App {
+    fn new(options: &SearchOptions) -> Self {
+        App {
+            sort: options.sort,
+            theme: options.theme.clone(),
+            keymap: options.keymap.clone(),
+            namespace: options.namespace.clone(),
+            risk: options.risk.clone(),
+            secrets: options.secrets.clone(),
+            reveal: options.reveal,
+            ..Default::default()
+        }
+    }
+
+    // what is drawn of the command, the secrets masked unless revealed.
+    fn shown_command(&self, data: &TagData) -> String {
+        if self.reveal {
+            data.command.clone()
+        } else {
+            self.secrets.masked(&data.command)
+        }
+    }
+
     fn move_cursor_left(&mut self, size: usize) {
         let cursor_moved_left = self.cursor_input_position.saturating_sub(size);
         self.cursor_input_position = self.clamp_cursor(cursor_moved_left);
@@ -141,22 +811,65 @@ impl App {
         new_cursor_pos.clamp(0, self.input.len())
     }
 
-    fn choose_suggestion(&mut self) {
-        cli_clipboard::set_contents(
-            self.suggestions[self.cursor_commnad_position]
-                .command
-                .clone(),
-        )
-        .unwrap();
+    fn is_marked(&self, tag: &str) -> bool {
+        self.marked.iter().any(|m| m.tag == tag)
+    }
+
+    // a namespace marks (or unmarks, when all are marked) every entry under it.
+    fn toggle_mark(&mut self) {
+        let entries: Vec<&TagData> = match self.rows.get(self.cursor_commnad_position) {
+            Some(Row::Entry { index, .. }) => vec![&self.suggestions[*index]],
+            Some(Row::Namespace { path, .. }) => self
+                .suggestions
+                .iter()
+                .filter(|data| data.tag.starts_with(path.as_str()))
+                .collect(),
+            None => return,
+        };
+        if entries.iter().all(|data| self.is_marked(&data.tag)) {
+            self.marked
+                .retain(|m| !entries.iter().any(|data| data.tag == m.tag));
+        } else {
+            let unmarked: Vec<TagData> = entries
+                .into_iter()
+                .filter(|data| !self.is_marked(&data.tag))
+                .cloned()
+                .collect();
+            self.marked.extend(unmarked);
+        }
+    }
+
+    // expands or collapses the highlighted namespace, false when it is an entry.
+    fn toggle_collapse(&mut self) -> bool {
+        let Some(Row::Namespace { path, .. }) = self.rows.get(self.cursor_commnad_position) else {
+            return false;
+        };
+        if !self.collapsed.remove(path) {
+            self.collapsed.insert(path.clone());
+        }
+        // the rows above the namespace stay, so the cursor stays on it.
+        self.rows = tree_rows(&self.suggestions, &self.collapsed);
+        true
+    }
+
+    // marked entries win over the highlighted one, in the order they were marked.
+    fn take_targets(&mut self) -> Vec<TagData> {
+        if !self.marked.is_empty() {
+            return std::mem::take(&mut self.marked);
+        }
+        match self.rows.get(self.cursor_commnad_position) {
+            Some(Row::Entry { index, .. }) => vec![self.suggestions[*index].clone()],
+            _ => Vec::new(),
+        }
     }
 
     fn auto_complete<T: TagDataRepository>(&mut self, repo: &T) {
-        self.suggestions.clear();
         self.cursor_commnad_position = 0;
-        let tags = search(repo, self.input.clone()).unwrap();
-        for tag in tags {
-            self.suggestions.push(tag);
-        }
+        let prefix = format!("{}{}", self.namespace, self.input);
+        self.suggestions = search(repo, &prefix).unwrap();
+        self.sort.apply(&mut self.suggestions);
+        self.risk.mark(&mut self.suggestions);
+        self.rows = tree_rows(&self.suggestions, &self.collapsed);
     }
 
     pub fn get_current_command_input(&self) -> usize {
@@ -164,20 +877,20 @@ impl App {
     }
 
     pub fn add_current_command_input(&mut self, add: i32) {
-        if self.suggestions.is_empty() {
+        if self.rows.is_empty() {
             self.cursor_commnad_position = 0;
             return;
         }
 
         if add > 0 {
-            if self.suggestions.len() - 1 <= self.cursor_commnad_position {
+            if self.rows.len() - 1 <= self.cursor_commnad_position {
                 self.cursor_commnad_position = 0;
                 return;
             }
             self.cursor_commnad_position += 1;
         } else {
             if self.cursor_commnad_position == 0 {
-                self.cursor_commnad_position = self.suggestions.len() - 1;
+                self.cursor_commnad_position = self.rows.len() - 1;
                 return;
             }
             self.cursor_commnad_position -= 1;
@@ -185,69 +898,245 @@ impl App {
     }
 }
 
-pub fn search_by_input<T>(repo: &T) -> Result<()>
+/// Runs the interactive search on the terminal and returns what was picked in it,
+/// the caller copies, prints, deletes or exports it.
+pub fn search_by_input<T>(repo: &T, options: &SearchOptions) -> Result<Outcome>
 where
     T: TagDataRepository,
 {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
+    let _guard = TerminalGuard::new(options.inline_height.is_some())?;
+    let viewport = match options.inline_height {
+        Some(height) => Viewport::Inline(height.max(MIN_INLINE_HEIGHT)),
+        None => Viewport::Fullscreen,
+    };
+    let mut terminal = Terminal::with_options(
+        CrosstermBackend::new(stdout()),
+        TerminalOptions { viewport },
+    )?;
+    let mut events = CrosstermEvents::new()?;
+    let outcome = run_app(&mut terminal, App::new(options), repo, &mut events);
+    if options.inline_height.is_some() {
+        clear_inline_viewport(&mut terminal)?;
+    }
+    Ok(outcome?)
+}
+
+/// The commands of the selected entries, joined as `options` asks.
+pub fn selected(targets: &[TagData], options: &SearchOptions) -> String {
+    let commands: Vec<&str> = targets.iter().map(|t| t.command.as_str()).collect();
+    commands.join(options.join.separator())
+}
+
+/// The marked entries as a JSON object of tags and commands, the secrets masked unless revealed.
+pub fn exported(mut targets: Vec<TagData>, options: &SearchOptions) -> Result<String> {
+    if !options.reveal {
+        options.secrets.mask(&mut targets);
+    }
+    let exported: serde_json::Map<String, serde_json::Value> = targets
+        .into_iter()
+        .map(|t| (t.tag, serde_json::Value::String(t.command)))
+        .collect();
+    Ok(serde_json::to_string_pretty(&exported)?)
+}
 
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+/// The input box and at least one search result.
+const MIN_INLINE_HEIGHT: u16 = 4;
 
-    let app = App::default();
+const TERMINAL_RESTORED: u8 = 0;
+const TERMINAL_FULLSCREEN: u8 = 1;
+const TERMINAL_INLINE: u8 = 2;
 
-    run_app(&mut terminal, app, repo)?;
+static TERMINAL_STATE: AtomicU8 = AtomicU8::new(TERMINAL_RESTORED);
+static INSTALL_PANIC_HOOK: Once = Once::new();
 
-    stdout().execute(LeaveAlternateScreen)?;
-    Ok(())
+fn restore_terminal() {
+    let state = TERMINAL_STATE.swap(TERMINAL_RESTORED, AtomicOrdering::SeqCst);
+    if state == TERMINAL_RESTORED {
+        return;
+    }
+    _ = disable_raw_mode();
+    if state == TERMINAL_FULLSCREEN {
+        _ = stdout().execute(LeaveAlternateScreen);
+    }
+    _ = stdout().execute(cursor::Show);
+}
+
+// wipes the picker and leaves the cursor where it was drawn, so the scrollback stays as it was.
+fn clear_inline_viewport<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+    let area = terminal.get_frame().size();
+    terminal.clear()?;
+    terminal.set_cursor(area.x, area.y)?;
+    terminal.show_cursor()
+}
+
+/// Puts the terminal into raw mode, on the alternate screen unless `inline`,
+/// and restores it on drop. The panic hook restores it as well before the panic message is printed.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new(inline: bool) -> io::Result<Self> {
+        INSTALL_PANIC_HOOK.call_once(|| {
+            let previous_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                previous_hook(info);
+            }));
+        });
+
+        let state = if inline {
+            TERMINAL_INLINE
+        } else {
+            TERMINAL_FULLSCREEN
+        };
+        TERMINAL_STATE.store(state, AtomicOrdering::SeqCst);
+        let guard = TerminalGuard;
+        enable_raw_mode()?;
+        if !inline {
+            stdout().execute(EnterAlternateScreen)?;
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Source of terminal events for `run_app` and the merge resolver, `None` ends them as cancelled.
+pub trait EventSource {
+    fn next_event(&mut self) -> io::Result<Option<Event>>;
+}
+
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The events of the terminal, ending when SIGINT or SIGTERM arrives.
+pub struct CrosstermEvents {
+    terminated: Arc<AtomicBool>,
+    signal_ids: Vec<SigId>,
+}
+
+impl CrosstermEvents {
+    pub fn new() -> io::Result<Self> {
+        let terminated = Arc::new(AtomicBool::new(false));
+        let mut signal_ids = Vec::new();
+        for signal in [SIGINT, SIGTERM] {
+            signal_ids.push(signal_hook::flag::register(signal, terminated.clone())?);
+        }
+        Ok(CrosstermEvents {
+            terminated,
+            signal_ids,
+        })
+    }
+}
+
+impl Drop for CrosstermEvents {
+    fn drop(&mut self) {
+        for id in self.signal_ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
+
+impl EventSource for CrosstermEvents {
+    fn next_event(&mut self) -> io::Result<Option<Event>> {
+        loop {
+            if self.terminated.load(AtomicOrdering::Relaxed) {
+                return Ok(None);
+            }
+            if event::poll(EVENT_POLL_INTERVAL)? {
+                return event::read().map(Some);
+            }
+        }
+    }
 }
 
-fn run_app<B: Backend, T: TagDataRepository>(
+fn run_app<B: Backend, T: TagDataRepository, E: EventSource>(
     terminal: &mut Terminal<B>,
     mut app: App,
     repo: &T,
-) -> io::Result<()> {
+    events: &mut E,
+) -> io::Result<Outcome> {
     app.auto_complete(repo);
     loop {
         terminal.draw(|f| render(f, &app))?;
 
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Enter => {
-                        if !app.suggestions.is_empty() {
-                            app.choose_suggestion();
-                            return Ok(());
+        let Some(event) = events.next_event()? else {
+            return Ok(Outcome::Cancel);
+        };
+        if let Event::Resize(_, _) = event {
+            terminal.autoresize()?;
+            continue;
+        }
+        if let Event::Key(key) = event {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(Outcome::Cancel);
+            }
+            if let Some(action) = app.keymap.action(&key) {
+                match action {
+                    Action::Select => {
+                        if app.marked.is_empty() && app.toggle_collapse() {
+                            continue;
+                        }
+                        let targets = app.take_targets();
+                        if !targets.is_empty() {
+                            return Ok(Outcome::Select(targets));
                         }
                     }
-                    KeyCode::Char(to_insert) => {
-                        app.enter_char(to_insert);
-                        app.auto_complete(repo);
-                    }
-                    KeyCode::Backspace => {
-                        app.delete_char();
-                        app.auto_complete(repo);
+                    Action::Delete => {
+                        let targets = app.take_targets();
+                        if !targets.is_empty() {
+                            return Ok(Outcome::Delete(
+                                targets.into_iter().map(|t| t.tag).collect(),
+                            ));
+                        }
                     }
-                    KeyCode::Left => {
-                        app.move_cursor_left(1);
+                    Action::Export => {
+                        let targets = app.take_targets();
+                        if !targets.is_empty() {
+                            return Ok(Outcome::Export(targets));
+                        }
                     }
-                    KeyCode::Right => {
-                        app.move_cursor_right(1);
+                    Action::ToggleMark => {
+                        app.toggle_mark();
+                        app.add_current_command_input(1);
                     }
-                    KeyCode::Tab => {
-                        app.auto_complete(repo);
+                    Action::ToggleMarkBack => {
+                        app.toggle_mark();
+                        app.add_current_command_input(-1);
                     }
-                    KeyCode::Down => {
+                    Action::Down => {
                         app.add_current_command_input(1);
                     }
-                    KeyCode::Up => {
+                    Action::Up => {
                         app.add_current_command_input(-1);
                     }
-                    KeyCode::Esc => {
-                        return Ok(());
+                    Action::Cancel => {
+                        return Ok(Outcome::Cancel);
                     }
-                    _ => {}
                 }
+                continue;
+            }
+            match key.code {
+                KeyCode::Char(to_insert) => {
+                    app.enter_char(to_insert);
+                    app.auto_complete(repo);
+                }
+                KeyCode::Backspace => {
+                    app.delete_char();
+                    app.auto_complete(repo);
+                }
+                KeyCode::Left => {
+                    app.move_cursor_left(1);
+                }
+                KeyCode::Right => {
+                    app.move_cursor_right(1);
+                }
+                _ => {}
             }
         }
     }
@@ -255,42 +1144,83 @@ fn run_app<B: Backend, T: TagDataRepository>(
 
 const TITLE_INPUT: &str = "Input";
 const TITLE_RESULT: &str = "Search results";
+const TITLE_PREVIEW: &str = "Command";
+
+/// A line of the key help: `keys` highlighted, then what they do.
+pub fn help_line(keys: String, description: &str) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{}:", keys), Style::new().bold()),
+        Span::raw(description.to_string()),
+        ".".into(),
+    ])
+}
 
 fn render(f: &mut Frame, app: &App) {
+    let key = |action| app.keymap.key_name(action);
     let text = vec![
-        Line::from(vec![
-            Span::styled("Press any key:", Style::new().bold()),
-            Span::raw("to start auto-complete tag and command,"),
-            ".".into(),
-        ]),
-        Line::from(vec![
-            Span::styled("key Left, key Right:", Style::new().bold()),
-            Span::raw("move cursor in INPUT window"),
-            ".".into(),
-        ]),
-        Line::from(vec![
-            Span::styled("key Up, key Down:", Style::new().bold()),
-            Span::raw("move cursor in Search results window"),
-            ".".into(),
-        ]),
-        Line::from(vec![
-            Span::styled("Enter:", Style::new().bold()),
-            Span::raw("to choose the command to clipboard and exit search mode"),
-            ".".into(),
-        ]),
-        Line::from(vec![
-            Span::styled("Esc:", Style::new().bold()),
-            Span::raw("to exit search mode"),
-            ".".into(),
-        ]),
+        help_line(
+            "Press any key".to_string(),
+            "to start auto-complete tag and command,",
+        ),
+        help_line(
+            "key Left, key Right".to_string(),
+            "move cursor in INPUT window",
+        ),
+        help_line(
+            format!("{}, {}", key(Action::Up), key(Action::Down)),
+            "move cursor in Search results window",
+        ),
+        help_line(
+            format!(
+                "{}, {}",
+                key(Action::ToggleMark),
+                key(Action::ToggleMarkBack)
+            ),
+            "mark or unmark the command and move to the next/previous one",
+        ),
+        help_line(
+            key(Action::Select).to_string(),
+            "to choose the marked (or current) commands and exit search mode",
+        ),
+        help_line(
+            key(Action::Select).to_string(),
+            "on a namespace to expand or collapse it",
+        ),
+        help_line(
+            format!("{}, {}", key(Action::Delete), key(Action::Export)),
+            "to delete or export the marked (or current) tags",
+        ),
+        help_line(
+            format!("{}, ctrl-c", key(Action::Cancel)),
+            "to exit search mode",
+        ),
     ];
 
+    // small viewports, like the inline one, only get the input and the results.
+    let help_height = if f.size().height >= text.len() as u16 + MIN_INLINE_HEIGHT * 2 {
+        text.len() as u16
+    } else {
+        0
+    };
+    // a multi-line command is shown in full below the results.
+    let preview = match app.rows.get(app.get_current_command_input()) {
+        Some(Row::Entry { index, .. }) if app.suggestions[*index].command.contains('\n') => {
+            Some(app.shown_command(&app.suggestions[*index]))
+        }
+        _ => None,
+    };
+    let room = f.size().height.saturating_sub(help_height + 3);
+    let preview_height = match &preview {
+        Some(command) if room >= 6 => (command.lines().count() as u16 + 2).min(room / 2),
+        _ => 0,
+    };
     let vertical = Layout::vertical([
-        Constraint::Length(text.len() as u16),
+        Constraint::Length(help_height),
         Constraint::Length(3),
         Constraint::Min(1),
+        Constraint::Length(preview_height),
     ]);
-    let [help_area, input_area, messages_area] = vertical.areas(f.size());
+    let [help_area, input_area, messages_area, preview_area] = vertical.areas(f.size());
     let help_message = Paragraph::new(text);
     f.render_widget(help_message, help_area);
 
@@ -304,20 +1234,78 @@ fn render(f: &mut Frame, app: &App) {
     );
 
     let messages: Vec<ListItem> = app
-        .suggestions
+        .rows
         .iter()
         .enumerate()
-        .map(|(i, m)| {
-            let content = if i == app.get_current_command_input() {
-                Line::from(Span::raw(format!("{0}: {1}", m.tag, m.command)).on_white())
-            } else {
-                Line::from(Span::raw(format!("{0}: {1}", m.tag, m.command)))
+        .map(|(i, row)| {
+            let mut span = match row {
+                Row::Namespace {
+                    path,
+                    depth,
+                    entries,
+                } => {
+                    let name = path.trim_end_matches(NAMESPACE_SEPARATOR);
+                    let name = name.rsplit(NAMESPACE_SEPARATOR).next().unwrap_or(name);
+                    let arrow = if app.collapsed.contains(path) {
+                        "▸"
+                    } else {
+                        "▾"
+                    };
+                    Span::raw(format!(
+                        "{}{} {}{} ({})",
+                        "  ".repeat(*depth),
+                        arrow,
+                        name,
+                        NAMESPACE_SEPARATOR,
+                        entries
+                    ))
+                }
+                Row::Entry { index, depth } => {
+                    let m = &app.suggestions[*index];
+                    let marked = app.is_marked(&m.tag);
+                    let risky = !m.risks.is_empty();
+                    let mark = match (marked, risky) {
+                        (true, _) => "* ",
+                        (false, true) => "! ",
+                        (false, false) => "  ",
+                    };
+                    // under a namespace the path is on the rows above.
+                    let name = m.tag.rsplit(NAMESPACE_SEPARATOR).next().unwrap_or(&m.tag);
+                    let shown = TagData {
+                        tag: name.to_string(),
+                        command: app.shown_command(m),
+                        ..m.clone()
+                    };
+                    let span = Span::raw(format!(
+                        "{}{}{}",
+                        "  ".repeat(*depth),
+                        mark,
+                        shown.summary()
+                    ));
+                    match (marked, risky) {
+                        (true, _) => span.fg(app.theme.marked),
+                        (false, true) => span.fg(app.theme.danger),
+                        (false, false) => span,
+                    }
+                }
             };
-
-            ListItem::new(content)
+            if i == app.get_current_command_input() {
+                span = span.bg(app.theme.highlight);
+            }
+            ListItem::new(Line::from(span))
         })
         .collect();
-    let messages =
-        List::new(messages).block(Block::default().borders(Borders::ALL).title(TITLE_RESULT));
+    let title = if app.marked.is_empty() {
+        TITLE_RESULT.to_string()
+    } else {
+        format!("{} ({} marked)", TITLE_RESULT, app.marked.len())
+    };
+    let messages = List::new(messages).block(Block::default().borders(Borders::ALL).title(title));
     f.render_widget(messages, messages_area);
+
+    if let (Some(command), true) = (preview.as_deref(), preview_height > 0) {
+        let preview = Paragraph::new(command)
+            .block(Block::default().borders(Borders::ALL).title(TITLE_PREVIEW));
+        f.render_widget(preview, preview_area);
+    }
 }
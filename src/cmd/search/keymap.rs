@@ -0,0 +1,179 @@
+use crate::config::KeyBindings;
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Select,
+    ToggleMark,
+    ToggleMarkBack,
+    Delete,
+    Export,
+    Up,
+    Down,
+    Cancel,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, KeyModifiers, Action)>,
+    names: Vec<(Action, String)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::new(&KeyBindings::default()).unwrap()
+    }
+}
+
+/// Parses a key such as `enter`, `x`, `ctrl-d` or `alt-shift-up`.
+pub fn parse_key(spec: &str) -> Result<(KeyCode, KeyModifiers)> {
+    let invalid = || anyhow!("invalid key: {}", spec);
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    // a trailing "-" is the minus key itself, not a separator.
+    while let Some((modifier, tail)) = rest.split_once('-').filter(|(_, tail)| !tail.is_empty()) {
+        modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(invalid()),
+        };
+        rest = tail;
+    }
+
+    let code = match rest.to_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "esc" => KeyCode::Esc,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        name if name.len() > 1 && name.starts_with('f') => {
+            KeyCode::F(name[1..].parse().map_err(|_| invalid())?)
+        }
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(invalid()),
+            }
+        }
+    };
+    Ok((code, modifiers))
+}
+
+impl KeyMap {
+    pub fn new(bindings: &KeyBindings) -> Result<Self> {
+        let actions = [
+            (&bindings.select, Action::Select),
+            (&bindings.toggle_mark, Action::ToggleMark),
+            (&bindings.toggle_mark_back, Action::ToggleMarkBack),
+            (&bindings.delete, Action::Delete),
+            (&bindings.export, Action::Export),
+            (&bindings.up, Action::Up),
+            (&bindings.down, Action::Down),
+            (&bindings.cancel, Action::Cancel),
+        ];
+        let mut keymap = KeyMap {
+            bindings: Vec::new(),
+            names: Vec::new(),
+        };
+        for (spec, action) in actions {
+            let (code, modifiers) = parse_key(spec)?;
+            keymap.bindings.push((code, modifiers, action));
+            keymap.names.push((action, spec.clone()));
+        }
+        Ok(keymap)
+    }
+
+    /// The key as written in the config, for the help text.
+    pub fn key_name(&self, action: Action) -> &str {
+        self.names
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default()
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        let pressed = normalize(key.code, key.modifiers);
+        self.bindings
+            .iter()
+            .find(|(code, modifiers, _)| normalize(*code, *modifiers) == pressed)
+            .map(|(_, _, action)| *action)
+    }
+}
+
+// terminals report shift on upper case letters and backtab inconsistently, the key code already tells.
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> (KeyCode, KeyModifiers) {
+    match code {
+        KeyCode::Char(_) | KeyCode::BackTab => (code, modifiers - KeyModifiers::SHIFT),
+        _ => (code, modifiers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            parse_key("enter").unwrap(),
+            (KeyCode::Enter, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse_key("ctrl-d").unwrap(),
+            (KeyCode::Char('d'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse_key("alt-shift-up").unwrap(),
+            (KeyCode::Up, KeyModifiers::ALT | KeyModifiers::SHIFT)
+        );
+        assert_eq!(
+            parse_key("ctrl--").unwrap(),
+            (KeyCode::Char('-'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parse_key("f5").unwrap(),
+            (KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert!(parse_key("ctrl-").is_err());
+        assert!(parse_key("hyper-x").is_err());
+        assert!(parse_key("xy").is_err());
+    }
+
+    #[test]
+    fn test_action() {
+        let bindings = KeyBindings {
+            down: "ctrl-j".to_string(),
+            ..Default::default()
+        };
+        let keymap = KeyMap::new(&bindings).unwrap();
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char('j'), KeyModifiers::CONTROL)),
+            Some(Action::Down)
+        );
+        assert_eq!(keymap.action(&key(KeyCode::Down, KeyModifiers::NONE)), None);
+        assert_eq!(
+            keymap.action(&key(KeyCode::BackTab, KeyModifiers::SHIFT)),
+            Some(Action::ToggleMarkBack)
+        );
+        assert_eq!(
+            keymap.action(&key(KeyCode::Char('d'), KeyModifiers::NONE)),
+            None
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use super::ALL_SUBCOMMAND;
+use crate::repo::error::RepoError;
+
+/// Separates the namespaces of a tag, as in `k8s/logs/tail`.
+pub const NAMESPACE_SEPARATOR: char = '/';
+
+// besides letters and digits.
+const PUNCTUATION: &[char] = &['-', '_', '.', ':', '@', '+'];
+
+/// Checks `tag` against the tag grammar: names separated by `/`, each made of
+/// letters, digits and `-_.:@+`, not starting with `-` or `.`. `all` is reserved.
+pub fn validate(tag: &str) -> Result<(), RepoError> {
+    if tag == ALL_SUBCOMMAND {
+        return Err(RepoError::Reserved(tag.to_string()));
+    }
+    let invalid = |reason: String| RepoError::InvalidTag {
+        tag: tag.escape_debug().to_string(),
+        reason,
+    };
+    if tag.is_empty() {
+        return Err(invalid("it is empty".to_string()));
+    }
+    for name in tag.split(NAMESPACE_SEPARATOR) {
+        if name.is_empty() {
+            return Err(invalid("a namespace or name is empty".to_string()));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| !c.is_alphanumeric() && !PUNCTUATION.contains(c))
+        {
+            return Err(invalid(format!("{:?} is not allowed", c)));
+        }
+        if name.starts_with(['-', '.']) {
+            return Err(invalid(format!("{} starts with {:?}", name, &name[..1])));
+        }
+    }
+    Ok(())
+}
+
+/// `namespace` as the prefix its tags start with, `k8s` and `k8s/` both give `k8s/`.
+pub fn namespace_prefix(namespace: &str) -> Result<String, RepoError> {
+    let namespace = namespace.trim_end_matches(NAMESPACE_SEPARATOR);
+    match validate(namespace) {
+        // a namespace can be called all, only a whole tag can not.
+        Ok(()) | Err(RepoError::Reserved(_)) => Ok(format!("{}{}", namespace, NAMESPACE_SEPARATOR)),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        for tag in [
+            "build",
+            "k8s/logs/tail",
+            "db:migrate",
+            "v1.2",
+            "user@host",
+            "日本語",
+        ] {
+            assert!(validate(tag).is_ok(), "{}", tag);
+        }
+        assert!(matches!(validate("all"), Err(RepoError::Reserved(_))));
+        assert!(validate("k8s/all").is_ok());
+
+        let reason = |tag| match validate(tag) {
+            Err(RepoError::InvalidTag { reason, .. }) => reason,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(reason(""), "it is empty");
+        assert_eq!(reason("two words"), "' ' is not allowed");
+        assert_eq!(reason("bell\u{7}"), "'\\u{7}' is not allowed");
+        assert_eq!(reason("--force"), "--force starts with \"-\"");
+        assert_eq!(reason("k8s/.hidden"), ".hidden starts with \".\"");
+        assert_eq!(reason("k8s//tail"), "a namespace or name is empty");
+        assert_eq!(reason("/k8s"), "a namespace or name is empty");
+        assert_eq!(reason("k8s/"), "a namespace or name is empty");
+    }
+
+    #[test]
+    fn test_namespace_prefix() {
+        assert_eq!(namespace_prefix("k8s").unwrap(), "k8s/");
+        assert_eq!(namespace_prefix("k8s/logs/").unwrap(), "k8s/logs/");
+        assert_eq!(namespace_prefix("all").unwrap(), "all/");
+        assert!(namespace_prefix("").is_err());
+        assert!(namespace_prefix("k 8s").is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use crate::cmd::risk::RiskRules;
+use crate::cmd::search::{keymap::KeyMap, Destination, Join, Sort};
+use crate::cmd::secret::SecretRules;
+use crate::cmd::OutputFormat;
+use crate::repo::layered_repository::GLOBAL_LAYER;
+use anyhow::{anyhow, Context, Result};
+use directories::BaseDirs;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+
+pub const COMMAND_NAME: &str = "tagcm";
+const DEFAULT_FILE_NAME: &str = "tags.json";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const ENV_CONFIG_PATH: &str = "TAGCM_CONFIG_PATH";
+const ENV_PREFIX: &str = "TAGCM_";
+
+/// Keys that have no value unless set, so they do not show up in the serialized defaults.
+const OPTIONAL_KEYS: [&str; 6] = [
+    "data_path",
+    "layers.team",
+    "sync.dir",
+    "sync.remote",
+    "update.proxy",
+    "update.ca_bundle",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[default]
+    Json,
+    /// JSON encrypted with a key derived from a passphrase.
+    Encrypted,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SearchMode {
+    #[default]
+    Fullscreen,
+    Inline,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub mode: SearchMode,
+    pub height: u16,
+    pub sort: Sort,
+    pub join: Join,
+    pub destination: Destination,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            mode: SearchMode::default(),
+            height: 10,
+            sort: Sort::default(),
+            join: Join::default(),
+            destination: Destination::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub format: OutputFormat,
+}
+
+/// Colors of the interactive search, any name or `#rrggbb` understood by ratatui.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub highlight: String,
+    pub marked: String,
+    /// Entries whose command looks dangerous.
+    pub danger: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            highlight: "white".to_string(),
+            marked: "yellow".to_string(),
+            danger: "red".to_string(),
+        }
+    }
+}
+
+/// Keys of the interactive search, e.g. `enter`, `tab`, `ctrl-d` or `alt-j`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub select: String,
+    pub toggle_mark: String,
+    pub toggle_mark_back: String,
+    pub delete: String,
+    pub export: String,
+    pub up: String,
+    pub down: String,
+    pub cancel: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            select: "enter".to_string(),
+            toggle_mark: "tab".to_string(),
+            toggle_mark_back: "backtab".to_string(),
+            delete: "ctrl-d".to_string(),
+            export: "ctrl-e".to_string(),
+            up: "up".to_string(),
+            down: "down".to_string(),
+            cancel: "esc".to_string(),
+        }
+    }
+}
+
+/// Stores stacked on top of the global data file, see `LayeredRepository`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayersConfig {
+    /// Path of a shared catalog, e.g. one checked into a team repository.
+    pub team: Option<String>,
+    /// Whether to use the `.tagcm.json` found in the current directory or its ancestors.
+    /// Off by default, a checked out repository could otherwise put its own commands
+    /// in front of the user's tags.
+    pub project: bool,
+    /// The layer `add` and `delete` change.
+    pub write: String,
+}
+
+impl Default for LayersConfig {
+    fn default() -> Self {
+        LayersConfig {
+            team: None,
+            project: false,
+            write: GLOBAL_LAYER.to_string(),
+        }
+    }
+}
+
+/// Where `sync` keeps the stores under git, see `tagcm sync`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// The git working tree, the stores of the layers kept in it are synchronized.
+    pub dir: Option<String>,
+    /// URL or path of the repository to pull from and push to.
+    pub remote: Option<String>,
+    pub branch: String,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            dir: None,
+            remote: None,
+            branch: "main".to_string(),
+        }
+    }
+}
+
+pub const RELEASES_API_URL: &str = "https://api.github.com/repos/oniku-2929/tagcm/releases";
+pub const ASSET_URL_TEMPLATE: &str =
+    "https://github.com/oniku-2929/tagcm/releases/download/{version}/{asset}";
+
+/// Which releases `update` offers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Channel {
+    /// Only releases, what `<api_url>/latest` points at.
+    #[default]
+    Stable,
+    /// Pre-releases too, whichever release has the highest version.
+    Beta,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UpdateConfig {
+    /// Refuse to install releases without a valid signature.
+    pub require_signature: bool,
+    /// GitHub compatible releases API to look for new versions.
+    pub api_url: String,
+    /// Download URL of release assets, `{version}` and `{asset}` are replaced.
+    pub asset_url: String,
+    /// `beta` also offers pre-releases.
+    pub channel: Channel,
+    /// Proxy url for update requests, HTTPS_PROXY / NO_PROXY are used when unset.
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates to trust.
+    pub ca_bundle: Option<String>,
+    pub connect_timeout_secs: u64,
+    pub read_timeout_secs: u64,
+    /// How often a failed request is retried, with a growing wait in between.
+    pub retries: u32,
+    /// Check for a new release at most this often and print a notice after other commands, 0 never checks.
+    pub check_interval_hours: u64,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            require_signature: false,
+            api_url: RELEASES_API_URL.to_string(),
+            asset_url: ASSET_URL_TEMPLATE.to_string(),
+            channel: Channel::default(),
+            proxy: None,
+            ca_bundle: None,
+            connect_timeout_secs: 10,
+            read_timeout_secs: 30,
+            retries: 3,
+            check_interval_hours: 0,
+        }
+    }
+}
+
+/// Which commands count as dangerous, see `RiskRules`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// Whether to use the built-in rules, e.g. for `rm -rf` or `kubectl delete`.
+    pub builtin: bool,
+    /// Regular expressions of more commands to treat as dangerous.
+    pub patterns: Vec<String>,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            builtin: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// What `add`, `edit` and `batch` do with a command that contains a secret.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretAction {
+    /// Ask whether to replace it on a terminal, otherwise warn.
+    #[default]
+    Ask,
+    /// Store it and warn.
+    Warn,
+    /// Replace it with a reference to an environment variable.
+    Redact,
+    /// Refuse the command.
+    Refuse,
+}
+
+/// Which parts of the commands count as secrets, see `SecretRules`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretConfig {
+    /// Whether to use the built-in rules, e.g. for bearer tokens or AWS keys.
+    pub builtin: bool,
+    /// Regular expressions of more secrets, the `secret` group if there is one.
+    pub patterns: Vec<String>,
+    pub on_add: SecretAction,
+}
+
+impl Default for SecretConfig {
+    fn default() -> Self {
+        SecretConfig {
+            builtin: true,
+            patterns: Vec::new(),
+            on_add: SecretAction::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_path: Option<String>,
+    pub backend: Backend,
+    /// Program and arguments `run` passes the command to.
+    pub shell: String,
+    pub search: SearchConfig,
+    pub output: OutputConfig,
+    pub theme: ThemeConfig,
+    pub keybindings: KeyBindings,
+    pub layers: LayersConfig,
+    pub risk: RiskConfig,
+    pub secret: SecretConfig,
+    pub sync: SyncConfig,
+    pub update: UpdateConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let shell = if cfg!(windows) { "cmd /C" } else { "sh -c" };
+        Config {
+            data_path: None,
+            backend: Backend::default(),
+            shell: shell.to_string(),
+            search: SearchConfig::default(),
+            output: OutputConfig::default(),
+            theme: ThemeConfig::default(),
+            keybindings: KeyBindings::default(),
+            layers: LayersConfig::default(),
+            risk: RiskConfig::default(),
+            secret: SecretConfig::default(),
+            sync: SyncConfig::default(),
+            update: UpdateConfig::default(),
+        }
+    }
+}
+
+fn config_dir() -> Option<PathBuf> {
+    BaseDirs::new().map(|base_dir| base_dir.config_dir().join(COMMAND_NAME))
+}
+
+pub fn get_config_path(config_path: Option<String>) -> PathBuf {
+    if let Some(path) = config_path {
+        return PathBuf::from(path);
+    }
+    if let Ok(path) = std::env::var(ENV_CONFIG_PATH) {
+        return PathBuf::from(path);
+    }
+    config_dir().unwrap_or_default().join(CONFIG_FILE_NAME)
+}
+
+fn default_data_path() -> String {
+    match config_dir() {
+        Some(dir) => dir.join(DEFAULT_FILE_NAME).display().to_string(),
+        None => String::new(),
+    }
+}
+
+/// `TAGCM_` followed by the key in upper case, e.g. `search.mode` is `TAGCM_SEARCH_MODE`.
+pub fn env_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase())
+}
+
+// values are read as TOML so numbers and booleans keep their type, anything else is a string.
+fn parse_value(raw: &str) -> Value {
+    match format!("value = {}", raw).parse::<Table>() {
+        Ok(mut table) => table
+            .remove("value")
+            .unwrap_or(Value::String(raw.to_string())),
+        Err(_) => Value::String(raw.to_string()),
+    }
+}
+
+fn flatten(prefix: &str, table: &Table, out: &mut Vec<(String, Value)>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Table(inner) => flatten(&key, inner, out),
+            _ => out.push((key, value.clone())),
+        }
+    }
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    match key.split_once('.') {
+        Some((section, rest)) => {
+            let inner = table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()));
+            match inner {
+                Value::Table(inner) => insert(inner, rest, value),
+                _ => Err(anyhow!("{} is not a section", section)),
+            }
+        }
+        None => {
+            table.insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+}
+
+/// Every key the config file accepts, in dotted form.
+pub fn known_keys() -> Vec<String> {
+    let mut entries = Vec::new();
+    flatten(
+        "",
+        &Table::try_from(Config::default()).unwrap(),
+        &mut entries,
+    );
+    let mut keys: Vec<String> = entries.into_iter().map(|(key, _)| key).collect();
+    keys.extend(OPTIONAL_KEYS.iter().map(|key| key.to_string()));
+    keys.sort();
+    keys
+}
+
+impl Config {
+    /// Reads the config file, if any, with `TAGCM_*` environment variables taking precedence over it.
+    pub fn load(path: &Path) -> Result<Config> {
+        let mut table = read_table(path)?;
+        for key in known_keys() {
+            if let Ok(raw) = std::env::var(env_name(&key)) {
+                insert(&mut table, &key, parse_value(&raw))?;
+            }
+        }
+        Config::from_table(table)
+    }
+
+    fn from_table(table: Table) -> Result<Config> {
+        let config: Config = Value::Table(table).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        KeyMap::new(&self.keybindings)?;
+        RiskRules::new(&self.risk)?;
+        SecretRules::new(&self.secret)?;
+        for color in [
+            &self.theme.highlight,
+            &self.theme.marked,
+            &self.theme.danger,
+        ] {
+            Color::from_str(color).map_err(|_| anyhow!("invalid color: {}", color))?;
+        }
+        Ok(())
+    }
+
+    pub fn data_path(&self) -> String {
+        self.data_path.clone().unwrap_or_else(default_data_path)
+    }
+
+    /// The effective value of every key, including the resolved data path.
+    pub fn entries(&self) -> Result<Vec<(String, Value)>> {
+        let mut table = Table::try_from(self)?;
+        table.insert("data_path".to_string(), Value::String(self.data_path()));
+        let mut entries = Vec::new();
+        flatten("", &table, &mut entries);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    pub fn get(&self, key: &str) -> Result<Value> {
+        self.entries()?
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+            .ok_or_else(|| anyhow!("unknown config key: {}", key))
+    }
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    if !path.exists() {
+        return Ok(Table::new());
+    }
+    Ok(fs::read_to_string(path)?.parse::<Table>()?)
+}
+
+/// Stores `raw` under `key` in the config file at `path`, refusing values the config would not load.
+/// A file that does not load already only has the new value checked, so it can be repaired.
+pub fn set(path: &Path, key: &str, raw: &str) -> Result<()> {
+    if !known_keys().iter().any(|k| k == key) {
+        return Err(anyhow!("unknown config key: {}", key));
+    }
+    let mut table = read_table(path)
+        .with_context(|| format!("failed to read {}, fix it by hand", path.display()))?;
+    let mut checked = match Config::from_table(table.clone()) {
+        Ok(_) => table.clone(),
+        Err(_) => Table::new(),
+    };
+    insert(&mut checked, key, parse_value(raw))?;
+    let value = match Config::from_table(checked.clone()) {
+        Ok(_) => parse_value(raw),
+        Err(_) => {
+            // enum and string settings also accept unquoted words.
+            insert(&mut checked, key, Value::String(raw.to_string()))?;
+            Config::from_table(checked)?;
+            Value::String(raw.to_string())
+        }
+    };
+    insert(&mut table, key, value)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, toml::to_string_pretty(&table)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("12"), Value::Integer(12));
+        assert_eq!(parse_value("true"), Value::Boolean(true));
+        assert_eq!(parse_value("inline"), Value::String("inline".to_string()));
+        assert_eq!(parse_value("\"sh -c\""), Value::String("sh -c".to_string()));
+    }
+
+    #[test]
+    fn test_set_and_load() {
+        let path = std::env::temp_dir().join(format!("tagcm_config_{}.toml", std::process::id()));
+        _ = fs::remove_file(&path);
+
+        set(&path, "search.mode", "inline").unwrap();
+        set(&path, "search.height", "7").unwrap();
+        set(&path, "shell", "bash -c").unwrap();
+        assert!(set(&path, "search.mode", "sideways").is_err());
+        assert!(set(&path, "search.speed", "1").is_err());
+        assert!(set(&path, "theme.highlight", "not-a-color").is_err());
+        assert!(set(&path, "keybindings.select", "ctrl-").is_err());
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.search.mode, SearchMode::Inline);
+        assert_eq!(config.search.height, 7);
+        assert_eq!(config.shell, "bash -c");
+        assert_eq!(config.search.sort, Sort::default());
+        assert_eq!(config.get("search.height").unwrap(), Value::Integer(7));
+        assert!(config.get("nothing").is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_set_repairs_a_broken_file() {
+        let path = std::env::temp_dir().join(format!("tagcm_broken_{}.toml", std::process::id()));
+        fs::write(&path, "[theme]\nhighlight = \"not-a-color\"\n").unwrap();
+        assert!(Config::load(&path).is_err());
+
+        set(&path, "search.mode", "inline").unwrap();
+        assert!(set(&path, "search.height", "high").is_err());
+        set(&path, "theme.highlight", "yellow").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.search.mode, SearchMode::Inline);
+
+        fs::write(&path, "[theme").unwrap();
+        assert!(set(&path, "search.mode", "inline").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_known_keys() {
+        let keys = known_keys();
+        assert!(keys.contains(&"data_path".to_string()));
+        assert!(keys.contains(&"keybindings.toggle_mark".to_string()));
+        assert!(keys.contains(&"layers.team".to_string()));
+        assert_eq!(env_name("search.mode"), "TAGCM_SEARCH_MODE");
+        assert_eq!(env_name("data_path"), "TAGCM_DATA_PATH");
+    }
+
+    #[test]
+    fn test_project_layer_is_opt_in() {
+        let layers = LayersConfig::default();
+        assert!(!layers.project);
+        assert_eq!(layers.write, GLOBAL_LAYER);
+    }
+}
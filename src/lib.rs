@@ -0,0 +1,34 @@
+//! tagcm keeps shell commands under short tags.
+//!
+//! The `tagcm` binary keeps its commands to itself and builds them on this crate:
+//! the stores, the search and the risk and secret rules, which other tools can use too:
+//!
+//! ```
+//! use tagcm::{search, HashMapRepository, TagDataRepository};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! # let dir = std::env::temp_dir().join(format!("tagcm_doc_{}", std::process::id()));
+//! # let path = dir.join("tags.json").display().to_string();
+//! let mut repo = HashMapRepository::new();
+//! repo.init(&path)?;
+//! repo.add_tag_data("deploy".to_string(), "make deploy".to_string())?;
+//!
+//! let found = search(&repo, "dep")?;
+//! assert_eq!(found[0].command, "make deploy");
+//! # std::fs::remove_dir_all(&dir)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`repo::open_layers`] opens the same project, team and global catalogs the CLI reads,
+//! with the settings of [`config::Config::load`].
+
+pub mod cmd;
+pub mod config;
+pub mod repo;
+
+pub use cmd::search::{search, Sort, TagData};
+pub use repo::error::RepoError;
+pub use repo::hashmap_repository::HashMapRepository;
+pub use repo::layered_repository::LayeredRepository;
+pub use repo::tag_data_repository::TagDataRepository;
@@ -1,12 +1,21 @@
-use std::path;
+mod cli;
 
-use anyhow::Result;
-use clap::Parser;
-use directories::BaseDirs;
-use repo::{hashmap_repository::HashMapRepository, tag_data_repository::TagDataRepository};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
-mod cmd;
-mod repo;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{ArgGroup, Parser};
+use cli::search::{Destination, Outcome};
+use ratatui::style::Color;
+use tagcm::config::{self, Config, SearchMode};
+use tagcm::repo;
+use tagcm::repo::error::RepoError;
+use tagcm::repo::journal::Journal;
+use tagcm::repo::tag_data_repository::TagDataRepository;
+use tagcm::LayeredRepository;
 
 #[derive(Parser)]
 struct Opts {
@@ -15,22 +24,107 @@ struct Opts {
 
     #[clap(long)]
     data_path: Option<String>,
+
+    #[clap(long)]
+    config_path: Option<String>,
+
+    /// The layer (global, team or project) that add and delete change
+    #[clap(long)]
+    layer: Option<String>,
 }
 
 #[derive(Parser)]
 enum Command {
     Add(Add),
+    /// Change the command of a tag in $VISUAL or $EDITOR
+    Edit(Edit),
     Delete(Delete),
     Show(Show),
     Search(Search),
     Version(Version),
     Update(Update),
+    Run(Run),
+    /// Rename a tag, keeping its command
+    Rename(Rename),
+    /// Store the command of a tag under another tag too
+    Copy(Copy),
+    /// Move a tag to another layer
+    Move(Move),
+    /// Take back the last change to the layer changes go to, `--layer` picks another one
+    Undo(UndoRedo),
+    /// Apply the last undone change of the layer changes go to again
+    Redo(UndoRedo),
+    /// Show the changes made to the tags, newest first
+    Log(Log),
+    /// Show the recorded changes of a tag with the commands it replaced
+    HistoryOf(HistoryOf),
+    /// Apply a script of add, delete and rename lines, all or none of them
+    Batch(Batch),
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+    /// Encrypt the store changes go to with a passphrase
+    Encrypt,
+    /// Write the encrypted store changes go to back in plain
+    Decrypt,
+    /// Keep the passphrase of encrypted stores in a background process
+    #[clap(subcommand)]
+    Agent(AgentCommand),
+    /// Commit the stores kept in sync.dir, pull from and push to sync.remote
+    Sync(Sync),
+    /// Merge two versions of a tag file tag by tag, usable as a git merge driver
+    Merge(Merge),
 }
 
 #[derive(Parser)]
+#[clap(group(ArgGroup::new("source").required(true).args(["command", "edit", "from_stdin"])))]
 struct Add {
     tag: String,
-    command: String,
+    command: Option<String>,
+
+    /// Write the command in $VISUAL or $EDITOR
+    #[clap(long)]
+    edit: bool,
+
+    /// Read the command from standard input
+    #[clap(long)]
+    from_stdin: bool,
+
+    /// Replace the command of an existing tag without asking
+    #[clap(long, short)]
+    force: bool,
+
+    #[clap(flatten)]
+    secrets: SecretArgs,
+}
+
+#[derive(Parser)]
+struct Edit {
+    tag: String,
+
+    #[clap(flatten)]
+    secrets: SecretArgs,
+}
+
+/// Overrides of `secret.on_add` for the commands that store commands.
+#[derive(clap::Args)]
+struct SecretArgs {
+    /// Replace secrets in the command with references to environment variables
+    #[clap(long)]
+    redact: bool,
+
+    /// Store secrets in the command as they are
+    #[clap(long, conflicts_with = "redact")]
+    keep_secrets: bool,
+}
+
+impl SecretArgs {
+    fn action(&self, config: &Config) -> config::SecretAction {
+        match (self.redact, self.keep_secrets) {
+            (true, _) => config::SecretAction::Redact,
+            (_, true) => config::SecretAction::Warn,
+            _ => config.secret.on_add,
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -40,88 +134,730 @@ struct Delete {
 
 #[derive(Parser)]
 struct Show {
+    /// `all`, a tag, or a namespace ending in `/` to show its subtree
     target: String,
     tag: Option<String>,
+
+    #[clap(long, value_enum)]
+    format: Option<cli::OutputFormat>,
+
+    /// Show secrets in the commands instead of masking them
+    #[clap(long)]
+    reveal: bool,
 }
 
 #[derive(Parser)]
 struct Search {
     search_str: Option<String>,
+
+    /// Only search the tags in this namespace, e.g. k8s or k8s/logs
+    #[clap(long)]
+    namespace: Option<String>,
+
+    /// How to join the commands when several entries are marked
+    #[clap(long, value_enum)]
+    join: Option<cli::search::Join>,
+
+    /// Where to send the chosen commands
+    #[clap(long, value_enum)]
+    output: Option<cli::search::Destination>,
+
+    /// Draw the interactive search in this many lines under the prompt instead of full screen
+    #[clap(long, conflicts_with = "fullscreen")]
+    height: Option<u16>,
+
+    /// Draw the interactive search on the whole screen even if the config says inline
+    #[clap(long)]
+    fullscreen: bool,
+
+    #[clap(long, value_enum)]
+    sort: Option<cli::search::Sort>,
+
+    #[clap(long, value_enum)]
+    format: Option<cli::OutputFormat>,
+
+    /// Show secrets in the commands instead of masking them
+    #[clap(long)]
+    reveal: bool,
+}
+
+#[derive(Parser)]
+struct Run {
+    tag: String,
+
+    /// Run it even if the command looks dangerous
+    #[clap(long, short)]
+    yes: bool,
+}
+
+#[derive(Parser)]
+struct Rename {
+    tag: String,
+    new_tag: String,
+
+    /// Replace the new tag if it exists
+    #[clap(long, short)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct Copy {
+    src: String,
+    dst: String,
+
+    /// Replace the destination tag if it exists
+    #[clap(long, short)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct Move {
+    tag: String,
+
+    /// The layer (global, team or project) to move the tag to
+    #[clap(long)]
+    to: String,
+
+    /// Replace the tag in the target layer if it exists there
+    #[clap(long, short)]
+    force: bool,
+}
+
+#[derive(Parser)]
+struct UndoRedo {
+    /// Show secrets in the commands instead of masking them
+    #[clap(long)]
+    reveal: bool,
+}
+
+#[derive(Parser)]
+struct Log {
+    #[clap(long, value_enum)]
+    format: Option<cli::OutputFormat>,
+
+    /// Show secrets in the commands instead of masking them
+    #[clap(long)]
+    reveal: bool,
+}
+
+#[derive(Parser)]
+struct HistoryOf {
+    tag: String,
+
+    #[clap(long, value_enum)]
+    format: Option<cli::OutputFormat>,
+
+    /// Show secrets in the commands instead of masking them
+    #[clap(long)]
+    reveal: bool,
+}
+
+#[derive(Parser)]
+struct Batch {
+    /// The script to read, `-` for standard input
+    file: PathBuf,
+
+    /// Let `add` lines replace existing tags
+    #[clap(long, short)]
+    force: bool,
+
+    #[clap(flatten)]
+    secrets: SecretArgs,
+}
+
+#[derive(Parser)]
+struct Sync {
+    /// Which side wins for a tag changed differently here and on the remote
+    #[clap(long, value_enum)]
+    prefer: Option<cli::sync::Prefer>,
+}
+
+#[derive(Parser)]
+struct Merge {
+    /// The version both sides started from, git's %O
+    base: String,
+    /// Our version, git's %A, the merge is written to it
+    ours: String,
+    /// Their version, git's %B
+    theirs: String,
+
+    /// Write the merge here instead of to ours
+    #[clap(long, short)]
+    output: Option<String>,
+
+    /// Which side wins for a tag changed differently on both, instead of asking
+    #[clap(long, value_enum)]
+    prefer: Option<cli::merge::Side>,
+}
+
+#[derive(Parser)]
+enum ConfigCommand {
+    /// Print the effective value of a key
+    Get { key: String },
+    /// Store a value in the config file
+    Set { key: String, value: String },
+    /// Print the effective value of every key
+    List,
+    /// Print the path of the config file
+    Path,
+}
+
+#[derive(Parser)]
+enum AgentCommand {
+    /// Ask for the passphrase once and keep it for a while
+    Start {
+        #[clap(long, default_value_t = 60)]
+        timeout_minutes: u64,
+    },
+    /// Forget the passphrase
+    Stop,
+    /// Tell whether the agent runs and for how long
+    Status,
+    /// The agent process itself, started by `agent start`
+    #[clap(hide = true)]
+    Serve {
+        #[clap(long)]
+        timeout_secs: u64,
+    },
 }
 
 #[derive(Parser)]
 struct Version {}
 
 #[derive(Parser)]
-struct Update {}
+struct Update {
+    /// Restore the binary replaced by the last update
+    #[clap(long)]
+    rollback: bool,
+
+    /// Install a release archive from disk instead of downloading it
+    #[clap(long, conflicts_with = "rollback")]
+    from_file: Option<PathBuf>,
+
+    /// Install an archive given with --from-file that has no checksum or signature
+    #[clap(long, requires = "from_file")]
+    skip_verify: bool,
+
+    /// Install without asking for confirmation
+    #[clap(long, short)]
+    yes: bool,
+
+    /// Only check for a new release, exits with 100 when one is available
+    #[clap(long, conflicts_with_all = ["rollback", "from_file"])]
+    check: bool,
+
+    /// Print the result of --check as JSON
+    #[clap(long, requires = "check")]
+    json: bool,
+
+    /// Install this release, e.g. 0.5.1, even if it is older than the running one
+    #[clap(long = "version", value_name = "VERSION", conflicts_with_all = ["rollback", "from_file", "check"])]
+    target_version: Option<String>,
+
+    /// Release channel to update from, overrides `update.channel`
+    #[clap(long, value_enum, conflicts_with_all = ["rollback", "from_file"])]
+    channel: Option<config::Channel>,
+}
 
-const COMMAND_NAME: &str = "tagcm";
-const DEFAULT_FILE_NAME: &str = "tags.json";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-const ENV_DATA_PATH: &str = "TAGCM_DATA_PATH";
+/// Exit status of `update --check` when a newer release exists.
+const EXIT_UPDATE_AVAILABLE: i32 = 100;
+const NOTICE_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn current_version() -> String {
+    if cfg!(debug_assertions) {
+        option_env!("TAGCM_OVERRIDE_VERSION")
+            .unwrap_or(VERSION)
+            .to_string()
+    } else {
+        VERSION.to_string()
+    }
+}
+
+fn update_options(config: &Config) -> cli::update::UpdateOptions {
+    cli::update::UpdateOptions {
+        require_signature: config.update.require_signature,
+        api_url: config.update.api_url.clone(),
+        asset_url_template: config.update.asset_url.clone(),
+        channel: config.update.channel,
+        proxy: config.update.proxy.clone(),
+        ca_bundle: config.update.ca_bundle.as_ref().map(PathBuf::from),
+        connect_timeout: Duration::from_secs(config.update.connect_timeout_secs),
+        read_timeout: Duration::from_secs(config.update.read_timeout_secs),
+        retries: config.update.retries,
+        ..Default::default()
+    }
+}
+
+// points at --force when a change was refused because the tag exists.
+fn hint_force<T, E: Into<anyhow::Error>>(result: Result<T, E>) -> Result<T> {
+    result.map_err(|e| {
+        let e = e.into();
+        match e.downcast_ref::<RepoError>() {
+            Some(RepoError::AlreadyExists(tag)) => {
+                anyhow!("tag {} already exists, use --force to replace it", tag)
+            }
+            _ => e,
+        }
+    })
+}
 
-fn get_data_path(data_path: Option<String>) -> String {
-    if let Some(path) = data_path {
-        return path;
+// the command that takes back the last change recorded in the journal of `layer`.
+fn undo_command(repo: &LayeredRepository, layer: &str) -> String {
+    match repo.write_layer() == layer {
+        true => "tagcm undo".to_string(),
+        false => format!("tagcm --layer {} undo", layer),
     }
-    if let Ok(path) = std::env::var(ENV_DATA_PATH) {
-        return path;
+}
+
+// how to take back a change recorded in the journal of `layer`, when plain `undo` does not.
+fn undo_hint(repo: &LayeredRepository, layer: &str) -> String {
+    match repo.write_layer() == layer {
+        true => String::new(),
+        false => format!(", `{}` takes it back", undo_command(repo, layer)),
     }
-    if let Some(base_dir) = BaseDirs::new() {
-        return path::Path::new(base_dir.config_dir().to_str().unwrap())
-            .join(COMMAND_NAME)
-            .join(DEFAULT_FILE_NAME)
-            .into_os_string()
-            .into_string()
-            .unwrap();
+}
+
+// copies `text` to the clipboard or prints it, as the search was told to.
+fn output(text: &str, destination: Destination) -> Result<()> {
+    match destination {
+        Destination::Clipboard => {
+            cli_clipboard::set_contents(text.to_string()).map_err(|e| anyhow!("{}", e))
+        }
+        Destination::Stdout => {
+            println!("{}", text);
+            Ok(())
+        }
     }
-    String::new()
+}
+
+fn search_options(opt: &Search, config: &Config) -> Result<cli::search::SearchOptions> {
+    let inline_height = match (opt.height, opt.fullscreen, config.search.mode) {
+        (Some(height), _, _) => Some(height),
+        (None, false, SearchMode::Inline) => Some(config.search.height),
+        _ => None,
+    };
+    let namespace = match &opt.namespace {
+        Some(namespace) => cli::tag::namespace_prefix(namespace)?,
+        None => String::new(),
+    };
+    // colors, keys and patterns were checked when the config was loaded.
+    Ok(cli::search::SearchOptions {
+        join: opt.join.unwrap_or(config.search.join),
+        destination: opt.output.unwrap_or(config.search.destination),
+        inline_height,
+        sort: opt.sort.unwrap_or(config.search.sort),
+        theme: cli::search::Theme {
+            highlight: Color::from_str(&config.theme.highlight).unwrap(),
+            marked: Color::from_str(&config.theme.marked).unwrap(),
+            danger: Color::from_str(&config.theme.danger).unwrap(),
+        },
+        keymap: cli::search::keymap::KeyMap::new(&config.keybindings).unwrap(),
+        namespace,
+        risk: cli::risk::RiskRules::new(&config.risk).unwrap(),
+        secrets: cli::secret::SecretRules::new(&config.secret).unwrap(),
+        reveal: opt.reveal,
+    })
 }
 
 fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
 
-    let mut repo: HashMapRepository = repo::hashmap_repository::HashMapRepository::new();
-    repo.init(&get_data_path(opts.data_path))?;
+    let config_path = config::get_config_path(opts.config_path);
+    // these also work with a config file that does not load, to repair it.
+    if let Command::Config(opt @ (ConfigCommand::Set { .. } | ConfigCommand::Path)) = &opts.command
+    {
+        match opt {
+            ConfigCommand::Set { key, value } => cli::config::set(&config_path, key, value)?,
+            _ => cli::config::path(&config_path),
+        }
+        return Ok(());
+    }
+    let config = Config::load(&config_path)?;
+    if let Command::Config(opt) = &opts.command {
+        match opt {
+            ConfigCommand::Get { key } => cli::config::get(&config, key)?,
+            ConfigCommand::List => cli::config::list(&config)?,
+            ConfigCommand::Set { .. } | ConfigCommand::Path => unreachable!(),
+        }
+        return Ok(());
+    }
+
+    if let Command::Version(_) | Command::Update(_) = &opts.command {
+        // these work on the binary, not on the stores.
+        match opts.command {
+            Command::Version(_) => {
+                println!("tagcm version: {}", VERSION);
+            }
+            Command::Update(opt) if opt.rollback => {
+                cli::update::rollback()?;
+            }
+            Command::Update(opt) if opt.check => {
+                let options = cli::update::UpdateOptions {
+                    channel: opt.channel.unwrap_or(config.update.channel),
+                    ..update_options(&config)
+                };
+                let status = cli::update::check(&current_version(), &options)?;
+                if opt.json {
+                    println!("{}", serde_json::to_string_pretty(&status)?);
+                } else if status.update_available {
+                    println!(
+                        "{} is available (current {})",
+                        status.latest, status.current
+                    );
+                } else {
+                    println!("this is the latest version ({})", status.current);
+                }
+                if status.update_available {
+                    std::process::exit(EXIT_UPDATE_AVAILABLE);
+                }
+            }
+            Command::Update(opt) => {
+                let options = cli::update::UpdateOptions {
+                    assume_yes: opt.yes,
+                    skip_verify: opt.skip_verify,
+                    target_version: opt.target_version,
+                    channel: opt.channel.unwrap_or(config.update.channel),
+                    ..update_options(&config)
+                };
+                match opt.from_file {
+                    Some(path) => cli::update::update_from_file(&path, &options)?,
+                    None => cli::update::update(&current_version(), &options)?,
+                }
+            }
+            _ => unreachable!(),
+        }
+        return Ok(());
+    }
+
+    let notice =
+        if config.update.check_interval_hours == 0 || matches!(opts.command, Command::Merge(_)) {
+            None
+        } else {
+            cli::update::notice::stamp_path().and_then(|stamp| {
+                cli::update::notice::spawn(
+                    &current_version(),
+                    &update_options(&config),
+                    Duration::from_secs(config.update.check_interval_hours * 60 * 60),
+                    &stamp,
+                )
+            })
+        };
+
+    if let Command::Merge(opt) = opts.command {
+        let output = opt.output.unwrap_or(opt.ours.clone());
+        let options = cli::merge::MergeOptions {
+            base: opt.base,
+            ours: opt.ours,
+            theirs: opt.theirs,
+            output: output.clone(),
+            prefer: opt.prefer,
+        };
+        let conflicts = cli::merge::merge_files(&options)?;
+        return cli::merge::check(&output, &conflicts);
+    }
+
+    let data_path = opts.data_path.unwrap_or_else(|| config.data_path());
+    let write_layer = opts.layer.clone().unwrap_or(config.layers.write.clone());
+    if let Command::Sync(opt) = &opts.command {
+        let paths: Vec<String> = repo::layer_paths(&config, &data_path, &write_layer)?
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        let report = cli::sync::sync_stores(&config.sync, &paths, opt.prefer)?;
+        if !report.conflicts.is_empty() {
+            println!(
+                "tags changed on both sides, kept the {} ones: {}",
+                match opt.prefer {
+                    Some(cli::sync::Prefer::Remote) => "remote",
+                    _ => "local",
+                },
+                report.conflicts.join(", ")
+            );
+        }
+        println!("{} commits received, {} sent", report.received, report.sent);
+        return Ok(());
+    }
+    if let Command::Encrypt | Command::Decrypt | Command::Agent(_) = &opts.command {
+        // these work on the store changes go to, without opening and decrypting the others.
+        let store_path = || repo::write_layer_path(&config, &data_path, &write_layer);
+        match opts.command {
+            Command::Encrypt => {
+                let path = store_path()?;
+                cli::encrypt::encrypt(&path, &repo::passphrase::choose(&path)?)?;
+                println!("{} encrypted", path);
+            }
+            Command::Decrypt => {
+                let path = store_path()?;
+                cli::encrypt::decrypt(&path, &repo::passphrase::get(&path)?)?;
+                println!("{} decrypted", path);
+            }
+            Command::Agent(AgentCommand::Start { timeout_minutes }) => {
+                cli::agent::start_for_store(&store_path()?, timeout_minutes)?;
+                println!(
+                    "agent started, it keeps the passphrase for {} minutes",
+                    timeout_minutes
+                );
+            }
+            Command::Agent(AgentCommand::Stop) => match cli::agent::stop()? {
+                true => println!("agent stopped"),
+                false => println!("no agent is running"),
+            },
+            Command::Agent(AgentCommand::Status) => match cli::agent::status()? {
+                Some(left) => println!(
+                    "agent running, it forgets the passphrase in {} min",
+                    left.as_secs().div_ceil(60)
+                ),
+                None => println!("no agent is running"),
+            },
+            Command::Agent(AgentCommand::Serve { timeout_secs }) => {
+                cli::agent::serve(Duration::from_secs(timeout_secs))?
+            }
+            _ => unreachable!(),
+        }
+        return Ok(());
+    }
+    let mut repo = repo::open_layers(&config, &data_path, opts.layer)?;
+    let journal =
+        Journal::for_store(&repo.file_path().unwrap_or(data_path)).with_cipher(repo.cipher());
+    let risk = cli::risk::RiskRules::new(&config.risk)?;
+    let secrets = cli::secret::SecretRules::new(&config.secret)?;
+    let recorder = cli::sync::Recorder::for_layers(&config.sync, &repo)?;
 
     match opts.command {
         Command::Add(opt) => {
-            cmd::add::add(opt.tag, opt.command, &mut repo)?;
+            let Some(command) = cli::add::command_to_add(&repo, &opt.tag, opt.command, opt.edit)?
+            else {
+                println!("add canceled");
+                return Ok(());
+            };
+            cli::tag::validate(&opt.tag)?;
+            let command = cli::screen(&secrets, &opt.tag, &command, opt.secrets.action(&config))?;
+            let added = cli::add::add(
+                opt.tag.clone(),
+                command.clone(),
+                &mut repo,
+                &journal,
+                opt.force,
+            );
+            let hidden = match added {
+                Err(RepoError::AlreadyExists(tag)) => {
+                    let old = repo.writable_data().remove(&tag).unwrap_or_default();
+                    let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+                    eprint!("{}", cli::add::diff(&old, &command, color));
+                    if !io::stdin().is_terminal() {
+                        bail!("tag {} already exists, use --force to replace it", tag);
+                    }
+                    if !cli::confirm(&format!("replace the command of {}?", tag))? {
+                        println!("add canceled");
+                        return Ok(());
+                    }
+                    cli::add::add(opt.tag.clone(), command.clone(), &mut repo, &journal, true)?
+                }
+                added => added?,
+            };
+            if let Some(layer) = hidden {
+                eprintln!(
+                    "{} stays hidden by the entry of the {} layer",
+                    opt.tag, layer
+                );
+            }
+            if let Some(warning) = risk.warning(&opt.tag, &command) {
+                eprintln!("{}", warning);
+            }
+        }
+        Command::Edit(opt) => {
+            let current = cli::show::show(&repo, opt.tag.clone())?;
+            match cli::edit::edit_command(&opt.tag, current.origin.as_deref(), &current.command)? {
+                Some(command) => {
+                    let command =
+                        cli::screen(&secrets, &opt.tag, &command, opt.secrets.action(&config))?;
+                    let hidden =
+                        cli::add::add(opt.tag.clone(), command.clone(), &mut repo, &journal, true)?;
+                    println!("Tag saved");
+                    if let Some(layer) = hidden {
+                        eprintln!(
+                            "{} stays hidden by the entry of the {} layer",
+                            opt.tag, layer
+                        );
+                    }
+                    if let Some(warning) = risk.warning(&opt.tag, &command) {
+                        eprintln!("{}", warning);
+                    }
+                }
+                None => println!("edit canceled"),
+            }
         }
         Command::Delete(opt) => {
-            cmd::delete::delete(&mut repo, opt.tag);
+            let origin = cli::delete::delete(&mut repo, &journal, &opt.tag)?;
+            println!("Tag deleted");
+            if let Some(origin) = origin {
+                println!("{} is still defined in the {} layer", opt.tag, origin);
+            }
         }
         Command::Show(opt) => {
-            if opt.target == "all" {
-                cmd::show::show_all(&repo);
-            } else {
-                cmd::show::show(&repo, opt.target);
-            }
+            let mut entries = cli::show::show_target(&repo, &opt.target)?;
+            cli::show::annotate(&mut entries, &risk, &secrets, opt.reveal);
+            let format = opt.format.unwrap_or(config.output.format);
+            print!("{}", cli::show::format(&entries, format)?);
         }
-        Command::Search(opt) => match opt.search_str {
-            Some(_) => {
-                let tags = cmd::search::search(&repo, opt.search_str.unwrap())?;
-                for tag in tags {
-                    println!("tag: {}, command: {}", tag.tag, tag.command);
-                }
+        Command::Search(opt) => match &opt.search_str {
+            Some(search_str) => {
+                let mut tags = cli::search::search_in(&repo, opt.namespace.as_deref(), search_str)?;
+                opt.sort.unwrap_or(config.search.sort).apply(&mut tags);
+                cli::show::annotate(&mut tags, &risk, &secrets, opt.reveal);
+                let format = opt.format.unwrap_or(config.output.format);
+                print!("{}", cli::search::format_results(&tags, format)?);
             }
             None => {
-                cmd::search::search_by_input(&repo)?;
+                let options = search_options(&opt, &config)?;
+                match cli::search::search_by_input(&repo, &options)? {
+                    Outcome::Cancel => {}
+                    Outcome::Select(targets) => {
+                        let action = match options.destination {
+                            Destination::Clipboard => "copy",
+                            Destination::Stdout => "print",
+                        };
+                        if !cli::approve(&targets, action, false)? {
+                            eprintln!("search canceled");
+                            return Ok(());
+                        }
+                        let text = cli::search::selected(&targets, &options);
+                        output(&text, options.destination)?;
+                    }
+                    Outcome::Delete(tags) => {
+                        cli::delete::delete_all(&mut repo, &journal, &tags)?;
+                        for tag in tags {
+                            println!("Tag deleted: {}", tag);
+                        }
+                    }
+                    Outcome::Export(targets) => {
+                        if !cli::approve(&targets, "export", false)? {
+                            eprintln!("search canceled");
+                            return Ok(());
+                        }
+                        output(
+                            &cli::search::exported(targets, &options)?,
+                            options.destination,
+                        )?;
+                    }
+                }
             }
         },
-        Command::Version(_) => {
-            println!("tagcm version: {}", VERSION);
-        }
-        Command::Update(_) => {
-            if cfg!(debug_assertions) {
-                let override_version = option_env!("TAGCM_OVERRIDE_VERSION")
-                    .unwrap_or(VERSION)
-                    .to_string();
-                cmd::update::update(&override_version)?
-            } else {
-                cmd::update::update(VERSION)?
+        Command::Run(opt) => {
+            let mut entry = [cli::show::show(&repo, opt.tag.clone())?];
+            risk.mark(&mut entry);
+            if !cli::approve(&entry, "run", opt.yes)? {
+                println!("run canceled");
+                return Ok(());
+            }
+            let status = cli::run::run(&repo, &opt.tag, &config.shell)?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
+            }
+        }
+        Command::Rename(opt) => {
+            let layer = hint_force(cli::rename::rename(
+                &mut repo,
+                &opt.tag,
+                &opt.new_tag,
+                opt.force,
+            ))?;
+            match repo.layer_names().len() {
+                1 => println!("Tag renamed"),
+                _ => println!(
+                    "Tag renamed in the {} layer{}",
+                    layer,
+                    undo_hint(&repo, &layer)
+                ),
+            }
+        }
+        Command::Copy(opt) => {
+            let layer = hint_force(cli::copy::copy(&mut repo, &opt.src, &opt.dst, opt.force))?;
+            match repo.layer_names().len() {
+                1 => println!("Tag copied"),
+                _ => println!(
+                    "Tag copied in the {} layer{}",
+                    layer,
+                    undo_hint(&repo, &layer)
+                ),
             }
         }
+        Command::Move(opt) => {
+            let from =
+                cli::move_tag::move_tag(&mut repo, &opt.tag, &opt.to, opt.force).map_err(|e| {
+                    match e.downcast_ref::<RepoError>() {
+                        Some(RepoError::AlreadyExists(tag)) => {
+                            anyhow!(
+                                "tag {} already exists in the {} layer, use --force to replace it",
+                                tag,
+                                opt.to
+                            )
+                        }
+                        _ => e,
+                    }
+                })?;
+            println!(
+                "Tag moved from the {} layer to the {} layer, `{}` and `{}` take it back",
+                from,
+                opt.to,
+                undo_command(&repo, &opt.to),
+                undo_command(&repo, &from)
+            );
+        }
+        Command::Undo(opt) => {
+            let secrets = (!opt.reveal).then_some(&secrets);
+            println!("{}", cli::undo::undo(&mut repo, &journal, secrets)?);
+        }
+        Command::Redo(opt) => {
+            let secrets = (!opt.reveal).then_some(&secrets);
+            println!("{}", cli::undo::redo(&mut repo, &journal, secrets)?);
+        }
+        Command::Log(opt) => {
+            let format = opt.format.unwrap_or(config.output.format);
+            let secrets = (!opt.reveal).then_some(&secrets);
+            print!("{}", cli::log::log(&journal, format, secrets)?);
+        }
+        Command::HistoryOf(opt) => {
+            let format = opt.format.unwrap_or(config.output.format);
+            let secrets = (!opt.reveal).then_some(&secrets);
+            print!(
+                "{}",
+                cli::history::history(&repo, &journal, &opt.tag, format, secrets)?
+            );
+        }
+        Command::Batch(opt) => {
+            let script = if opt.file.as_os_str() == "-" {
+                io::read_to_string(io::stdin())?
+            } else {
+                std::fs::read_to_string(&opt.file)
+                    .with_context(|| format!("failed to read {}", opt.file.display()))?
+            };
+            let action = opt.secrets.action(&config);
+            let count =
+                cli::batch::batch(&mut repo, &journal, &script, opt.force, |tag, command| {
+                    cli::screen(&secrets, tag, command, action)
+                })?;
+            println!("{} operations applied", count);
+        }
+        Command::Config(_)
+        | Command::Encrypt
+        | Command::Decrypt
+        | Command::Agent(_)
+        | Command::Sync(_)
+        | Command::Merge(_)
+        | Command::Version(_)
+        | Command::Update(_) => unreachable!(),
+    }
+
+    if let Some(recorder) = recorder {
+        // the change is made, failing to commit it only delays it to the next sync.
+        if let Err(e) = recorder.commit() {
+            eprintln!("warning: failed to commit the change: {:#}", e);
+        }
+    }
+    if let Some(notice) = notice.and_then(|notice| notice.wait(NOTICE_TIMEOUT)) {
+        eprintln!("{}", notice);
     }
     Ok(())
 }
@@ -1,3 +1,140 @@
+pub mod cipher;
+pub mod encrypted_repository;
+pub mod error;
 pub mod hashmap_repository;
+pub mod journal;
+pub mod layered_repository;
+pub mod passphrase;
 pub mod tag_data_repository;
+#[cfg(test)]
 pub mod unittest_repository;
+
+use crate::config::{Backend, Config};
+use anyhow::Result;
+use encrypted_repository::EncryptedRepository;
+use error::RepoError;
+use hashmap_repository::HashMapRepository;
+use layered_repository::LayeredRepository;
+use std::io::Write;
+use std::path::Path;
+use tag_data_repository::TagDataRepository;
+
+/// Replaces the file at `path` with `text` at once: it is written next to it and renamed
+/// over it, so a failed write leaves the old file whole. An existing file keeps its
+/// permissions, a new one is readable by the user only.
+pub fn replace_file(path: &str, text: &str) -> Result<(), RepoError> {
+    let io = |e| RepoError::io(path, e);
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir).map_err(io)?;
+    file.write_all(text.as_bytes()).map_err(io)?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        file.as_file()
+            .set_permissions(metadata.permissions())
+            .map_err(io)?;
+    }
+    file.as_file().sync_all().map_err(io)?;
+    file.persist(path).map_err(|e| io(e.error))?;
+    Ok(())
+}
+
+/// Opens the store at `path`. An existing file is read as what it holds, a new one
+/// is made with the backend `config` selects.
+pub fn open_store(config: &Config, path: &str) -> Result<Box<dyn TagDataRepository>> {
+    let encrypted = match std::path::Path::new(path).exists() {
+        true => encrypted_repository::is_encrypted(path),
+        false => config.backend == Backend::Encrypted,
+    };
+    let mut repo: Box<dyn TagDataRepository> = match encrypted {
+        true => Box::new(EncryptedRepository::new()),
+        false => Box::new(HashMapRepository::new()),
+    };
+    repo.init(path)?;
+    Ok(repo)
+}
+
+/// Where the catalogs the CLI works with are kept: the project file found from the
+/// current directory, the team catalog and the data file at `data_path`, in that order.
+/// The project file is only used with `layers.project` or when `write_layer` is the project,
+/// and is also given when `write_layer` is the project and there is none yet.
+pub fn layer_paths(
+    config: &Config,
+    data_path: &str,
+    write_layer: &str,
+) -> Result<Vec<(&'static str, String)>> {
+    let mut paths = Vec::new();
+    if config.layers.project || write_layer == layered_repository::PROJECT_LAYER {
+        let cwd = std::env::current_dir()?;
+        let mut project_path = layered_repository::find_project_file(&cwd);
+        if project_path.is_none() && write_layer == layered_repository::PROJECT_LAYER {
+            project_path = Some(cwd.join(layered_repository::PROJECT_FILE_NAME));
+        }
+        if let Some(path) = project_path {
+            paths.push((
+                layered_repository::PROJECT_LAYER,
+                path.display().to_string(),
+            ));
+        }
+    }
+    if let Some(path) = &config.layers.team {
+        paths.push((layered_repository::TEAM_LAYER, path.clone()));
+    }
+    paths.push((layered_repository::GLOBAL_LAYER, data_path.to_string()));
+    Ok(paths)
+}
+
+/// Where the store of `write_layer` is kept, for commands that work on it alone.
+pub fn write_layer_path(config: &Config, data_path: &str, write_layer: &str) -> Result<String> {
+    layer_paths(config, data_path, write_layer)?
+        .into_iter()
+        .find(|(name, _)| *name == write_layer)
+        .map(|(_, path)| path)
+        .ok_or_else(|| anyhow::anyhow!("layer {} is not configured", write_layer))
+}
+
+/// Opens the catalogs of `layer_paths`.
+/// `layer` (or `layers.write` of `config`) names the one changes go to.
+pub fn open_layers(
+    config: &Config,
+    data_path: &str,
+    layer: Option<String>,
+) -> Result<LayeredRepository> {
+    let write_layer = layer.unwrap_or(config.layers.write.clone());
+    let mut repo = LayeredRepository::new();
+    for (name, path) in layer_paths(config, data_path, &write_layer)? {
+        repo.add_layer(name, open_store(config, &path)?);
+    }
+    repo.set_write_layer(&write_layer)?;
+    Ok(repo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_replace_file() {
+        let dir = std::env::temp_dir().join(format!("tagcm_replace_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tags.json").display().to_string();
+        replace_file(&path, "{}").unwrap();
+        replace_file(&path, r#"{"a": "b"}"#).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"a": "b"}"#);
+        // nothing is left next to it.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &str| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&path), 0o600);
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            replace_file(&path, "{}").unwrap();
+            assert_eq!(mode(&path), 0o644);
+        }
+        assert!(replace_file(&dir.join("missing/tags.json").display().to_string(), "{}").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use super::error::RepoError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// How hard the key is to derive from the passphrase, kept with what it encrypts.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct KdfParams {
+    /// Memory in KiB.
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The Argon2id parameters OWASP recommends.
+    fn default() -> Self {
+        KdfParams {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+/// A key derived from a passphrase with Argon2id, encrypting with XChaCha20-Poly1305.
+#[derive(Clone)]
+pub struct Cipher {
+    key: [u8; 32],
+    salt: Vec<u8>,
+    params: KdfParams,
+}
+
+impl Cipher {
+    /// A key for `passphrase` with a new random salt.
+    pub fn new(passphrase: &str) -> Cipher {
+        let mut salt = vec![0; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        // the default parameters and a fresh salt are always accepted.
+        Cipher::derive(passphrase, &salt, KdfParams::default()).unwrap()
+    }
+
+    /// The key for `passphrase` with the `salt` and `params` it was made with.
+    pub fn derive(passphrase: &str, salt: &[u8], params: KdfParams) -> Result<Cipher, String> {
+        let argon_params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+            .map_err(|e| e.to_string())?;
+        let mut key = [0; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| e.to_string())?;
+        Ok(Cipher {
+            key,
+            salt: salt.to_vec(),
+            params,
+        })
+    }
+
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    pub fn params(&self) -> KdfParams {
+        self.params
+    }
+
+    /// `plaintext` encrypted under a new random nonce, as base64 of the nonce and the ciphertext.
+    pub fn seal(&self, plaintext: &[u8]) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // encrypting only fails for inputs far larger than a tag store.
+        let ciphertext = XChaCha20Poly1305::new(&self.key.into())
+            .encrypt(&nonce, plaintext)
+            .unwrap();
+        STANDARD.encode([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// What `seal` made `sealed` from, an error naming `path` when the key is not the one
+    /// it was sealed with or `sealed` was changed.
+    pub fn open(&self, sealed: &str, path: &str) -> Result<Vec<u8>, RepoError> {
+        let error = || RepoError::Decrypt(path.to_string());
+        let bytes = STANDARD.decode(sealed.trim()).map_err(|_| error())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(error());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        XChaCha20Poly1305::new(&self.key.into())
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| error())
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_params() -> KdfParams {
+    // fast enough for tests, far too weak for anything else.
+    KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let cipher = Cipher::derive("passphrase", b"0123456789abcdef", test_params()).unwrap();
+        let sealed = cipher.seal(b"secret data");
+        assert_eq!(cipher.open(&sealed, "x").unwrap(), b"secret data");
+        // every seal takes a new nonce.
+        assert_ne!(sealed, cipher.seal(b"secret data"));
+
+        let same = Cipher::derive("passphrase", cipher.salt(), cipher.params()).unwrap();
+        assert_eq!(same.open(&sealed, "x").unwrap(), b"secret data");
+        let wrong = Cipher::derive("wrong", cipher.salt(), cipher.params()).unwrap();
+        assert!(matches!(
+            wrong.open(&sealed, "x"),
+            Err(RepoError::Decrypt(path)) if path == "x"
+        ));
+        assert!(cipher.open("not base64!", "x").is_err());
+        assert!(cipher.open("AAAA", "x").is_err());
+    }
+
+    #[test]
+    fn test_new_salts_differ() {
+        assert_ne!(Cipher::new("a").salt(), Cipher::new("a").salt());
+        assert!(Cipher::derive("a", b"short", test_params()).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use super::cipher::{Cipher, KdfParams};
+use super::error::RepoError;
+use super::passphrase;
+use super::tag_data_repository::TagDataRepository;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Marks a data file as encrypted, a plain one is a map of tags to commands.
+pub const FORMAT: &str = "tagcm-encrypted";
+const VERSION: u32 = 1;
+const KDF: &str = "argon2id";
+const CIPHER: &str = "xchacha20poly1305";
+
+/// What an encrypted data file holds: how to derive the key and the sealed tags.
+#[derive(Debug, Deserialize, Serialize)]
+struct Envelope {
+    format: String,
+    version: u32,
+    kdf: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    salt: String,
+    cipher: String,
+    data: String,
+}
+
+/// Whether the data file at `path` is an encrypted one.
+pub fn is_encrypted(path: &str) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str::<Envelope>(&text).ok())
+        .is_some_and(|envelope| envelope.format == FORMAT)
+}
+
+fn unsupported(path: &str, what: String) -> RepoError {
+    RepoError::Parse {
+        path: path.to_string(),
+        source: serde::de::Error::custom(what),
+    }
+}
+
+/// Tags kept in a file encrypted with a key derived from a passphrase.
+pub struct EncryptedRepository {
+    data: HashMap<String, String>,
+    file_path: String,
+    passphrase: Option<String>,
+    cipher: Option<Cipher>,
+}
+
+impl EncryptedRepository {
+    /// A store that `init` opens with `passphrase` instead of looking for one.
+    pub fn with_passphrase(passphrase: &str) -> Self {
+        EncryptedRepository {
+            passphrase: Some(passphrase.to_string()),
+            ..EncryptedRepository::new()
+        }
+    }
+
+    /// Writes `data` to `file_path` encrypted with `cipher`, replacing what is there.
+    pub fn create(
+        file_path: &str,
+        data: HashMap<String, String>,
+        cipher: Cipher,
+    ) -> Result<Self, RepoError> {
+        let repo = EncryptedRepository {
+            data,
+            file_path: file_path.to_string(),
+            passphrase: None,
+            cipher: Some(cipher),
+        };
+        repo.save()?;
+        Ok(repo)
+    }
+
+    /// Every tag with its command, to write them out in plain.
+    pub fn into_data(self) -> HashMap<String, String> {
+        self.data
+    }
+
+    fn open(&mut self, file_path: &str) -> Result<(), RepoError> {
+        let text = fs::read_to_string(file_path).map_err(|e| RepoError::io(file_path, e))?;
+        let (data, cipher) = decrypt_text(&text, file_path, || match self.passphrase.take() {
+            Some(passphrase) => Ok(passphrase),
+            None => passphrase::get(file_path),
+        })?;
+        self.data = data;
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+}
+
+/// The tags of the encrypted store `text` read from `path` and the key they were
+/// encrypted with, derived from what `passphrase` gives.
+pub fn decrypt_text(
+    text: &str,
+    path: &str,
+    passphrase: impl FnOnce() -> Result<String, RepoError>,
+) -> Result<(HashMap<String, String>, Cipher), RepoError> {
+    let envelope: Envelope = serde_json::from_str(text).map_err(|source| RepoError::Parse {
+        path: path.to_string(),
+        source,
+    })?;
+    if envelope.format != FORMAT || envelope.version != VERSION {
+        return Err(unsupported(
+            path,
+            format!(
+                "unsupported format {} {}",
+                envelope.format, envelope.version
+            ),
+        ));
+    }
+    if envelope.kdf != KDF || envelope.cipher != CIPHER {
+        return Err(unsupported(
+            path,
+            format!(
+                "unsupported encryption {} {}",
+                envelope.kdf, envelope.cipher
+            ),
+        ));
+    }
+    let salt = STANDARD
+        .decode(&envelope.salt)
+        .map_err(|_| RepoError::Decrypt(path.to_string()))?;
+    let cipher =
+        Cipher::derive(&passphrase()?, &salt, envelope.params).map_err(|e| unsupported(path, e))?;
+    let plaintext = cipher.open(&envelope.data, path)?;
+    let data = serde_json::from_slice(&plaintext).map_err(|source| RepoError::Parse {
+        path: path.to_string(),
+        source,
+    })?;
+    Ok((data, cipher))
+}
+
+/// `data` encrypted with `cipher`, as the text of an encrypted store.
+pub fn encrypt_text(data: &HashMap<String, String>, cipher: &Cipher) -> String {
+    // a map of strings always serializes.
+    let plaintext = serde_json::to_vec(data).unwrap();
+    let envelope = Envelope {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        kdf: KDF.to_string(),
+        params: cipher.params(),
+        salt: STANDARD.encode(cipher.salt()),
+        cipher: CIPHER.to_string(),
+        data: cipher.seal(&plaintext),
+    };
+    serde_json::to_string_pretty(&envelope).unwrap()
+}
+
+impl TagDataRepository for EncryptedRepository {
+    fn new() -> Self {
+        EncryptedRepository {
+            data: HashMap::new(),
+            file_path: String::new(),
+            passphrase: None,
+            cipher: None,
+        }
+    }
+
+    /// Opens the store at `file_path`, or creates an empty one with a new passphrase.
+    fn init(&mut self, file_path: &str) -> Result<(), RepoError> {
+        self.file_path = Path::new(file_path).display().to_string();
+        if Path::new(file_path).exists() {
+            return self.open(file_path);
+        }
+        if let Some(parent) = Path::new(file_path).parent() {
+            fs::create_dir_all(parent).map_err(|e| RepoError::io(file_path, e))?;
+        }
+        let passphrase = match self.passphrase.take() {
+            Some(passphrase) => passphrase,
+            None => passphrase::choose(file_path)?,
+        };
+        self.cipher = Some(Cipher::new(&passphrase));
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), RepoError> {
+        // init or create always leave a key.
+        let text = encrypt_text(&self.data, self.cipher.as_ref().unwrap());
+        super::replace_file(&self.file_path, &text)
+    }
+
+    fn get_tag_data(&self, tag: &str) -> Option<String> {
+        self.data.get(tag).cloned()
+    }
+
+    fn get_all_tags(&self) -> Vec<String> {
+        self.data.keys().cloned().collect()
+    }
+
+    fn get_all_data(&self) -> Vec<(String, String)> {
+        self.data
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    fn file_path(&self) -> Option<String> {
+        Some(self.file_path.clone())
+    }
+
+    fn cipher(&self) -> Option<Cipher> {
+        self.cipher.clone()
+    }
+
+    fn add_tag_data(&mut self, tag: String, command: String) -> Result<(), RepoError> {
+        self.data.insert(tag, command);
+        self.save()
+    }
+
+    fn remove_tag_data(&mut self, tag: &str) -> Result<String, RepoError> {
+        let command = self
+            .data
+            .remove(tag)
+            .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+        self.save()?;
+        Ok(command)
+    }
+
+    fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), RepoError> {
+        self.data = data;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::cipher::test_params;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "tagcm_encrypted_{}_{}.json",
+            name,
+            std::process::id()
+        ));
+        _ = fs::remove_file(&path);
+        path.display().to_string()
+    }
+
+    fn cipher(passphrase: &str) -> Cipher {
+        Cipher::derive(passphrase, b"0123456789abcdef", test_params()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round_trip");
+        let data = HashMap::from([("login".to_string(), "TOKEN=s3cr3t ./login".to_string())]);
+        let mut repo = EncryptedRepository::create(&path, data, cipher("right")).unwrap();
+        repo.add_tag_data("ls".to_string(), "ls -la".to_string())
+            .unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("s3cr3t") && !text.contains("login"));
+        assert!(is_encrypted(&path));
+
+        let mut repo = EncryptedRepository::with_passphrase("right");
+        repo.init(&path).unwrap();
+        assert_eq!(repo.get_tag_data("login").unwrap(), "TOKEN=s3cr3t ./login");
+        assert_eq!(repo.get_tag_data("ls").unwrap(), "ls -la");
+        assert!(repo.cipher().is_some());
+
+        let mut repo = EncryptedRepository::with_passphrase("wrong");
+        assert!(matches!(repo.init(&path), Err(RepoError::Decrypt(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_file() {
+        let path = temp_path("tampered");
+        EncryptedRepository::create(&path, HashMap::new(), cipher("p")).unwrap();
+        let mut envelope: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        envelope["version"] = 2.into();
+        fs::write(&path, envelope.to_string()).unwrap();
+        let mut repo = EncryptedRepository::with_passphrase("p");
+        assert!(matches!(repo.init(&path), Err(RepoError::Parse { .. })));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_plain_file_is_not_encrypted() {
+        let path = temp_path("plain");
+        assert!(!is_encrypted(&path));
+        fs::write(&path, r#"{"format": "tagcm-encrypted"}"#).unwrap();
+        assert!(!is_encrypted(&path));
+        fs::remove_file(&path).unwrap();
+    }
+}
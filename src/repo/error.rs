@@ -0,0 +1,58 @@
+use std::io;
+use thiserror::Error;
+
+/// Why a store could not be read or changed.
+#[derive(Debug, Error)]
+pub enum RepoError {
+    #[error("tag {0} not found")]
+    NotFound(String),
+    /// The tag exists, but in a layer other than the one changes go to.
+    #[error("tag {tag} is defined in the {layer} layer, changes go to the {write_layer} layer")]
+    OtherLayer {
+        tag: String,
+        layer: String,
+        write_layer: String,
+    },
+    #[error("tag {0} already exists")]
+    AlreadyExists(String),
+    #[error("tag {0} is reserved")]
+    Reserved(String),
+    #[error("invalid tag {tag}: {reason}")]
+    InvalidTag { tag: String, reason: String },
+    #[error("failed to access {path}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to decrypt {0}, is the passphrase right?")]
+    Decrypt(String),
+    /// The store is encrypted and there was no way to get its passphrase.
+    #[error("no passphrase for {0}, set TAGCM_PASSPHRASE, start the agent with `tagcm agent start` or run tagcm in a terminal")]
+    NoPassphrase(String),
+    #[error("the passphrases for {0} do not match")]
+    PassphraseMismatch(String),
+    /// A change failed after the store was saved, and so did putting the store back.
+    #[error("failed to restore {path} after an error: {rollback}")]
+    Rollback {
+        path: String,
+        #[source]
+        source: Box<RepoError>,
+        rollback: Box<RepoError>,
+    },
+    #[error("failed to parse {path}")]
+    Parse {
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl RepoError {
+    pub fn io(path: &str, source: io::Error) -> Self {
+        RepoError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
@@ -1,10 +1,18 @@
+use super::error::RepoError;
 use super::tag_data_repository::TagDataRepository;
-use anyhow::{Ok, Result};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::io::prelude::*;
 use std::path;
 
+/// `data` as the text of a plain store, sorted so a store kept under version control
+/// changes only where its tags do.
+pub fn plain_text(data: &HashMap<String, String>) -> String {
+    let sorted: BTreeMap<_, _> = data.iter().collect();
+    // a map of strings always serializes.
+    serde_json::to_string_pretty(&sorted).unwrap()
+}
+
 pub struct HashMapRepository {
     data: HashMap<String, String>,
     file_path: String,
@@ -18,28 +26,31 @@ impl TagDataRepository for HashMapRepository {
         }
     }
 
-    fn init(&mut self, file_path: &str) -> Result<()> {
+    fn init(&mut self, file_path: &str) -> Result<(), RepoError> {
         let data_path = path::Path::new(file_path);
+        let io = |e| RepoError::io(file_path, e);
         if !data_path.exists() {
-            fs::create_dir_all(data_path.parent().unwrap())?;
-            let mut file = File::create(file_path)?;
-            file.write_all(b"{}")?;
+            if let Some(parent) = data_path.parent() {
+                fs::create_dir_all(parent).map_err(io)?;
+            }
+            let mut file = File::create(file_path).map_err(io)?;
+            file.write_all(b"{}").map_err(io)?;
         }
         self.file_path = data_path.display().to_string();
-        let file = File::open(file_path)?;
-        self.data = serde_json::from_reader(file)?;
+        let file = File::open(file_path).map_err(io)?;
+        self.data = serde_json::from_reader(file).map_err(|source| RepoError::Parse {
+            path: file_path.to_string(),
+            source,
+        })?;
         Ok(())
     }
 
-    fn save(&self) -> Result<()> {
-        let serialized = serde_json::to_string_pretty(&self.data)?;
-        let mut file = File::create(self.file_path.as_str())?;
-        file.write_all(serialized.as_bytes())?;
-        Ok(())
+    fn save(&self) -> Result<(), RepoError> {
+        super::replace_file(&self.file_path, &plain_text(&self.data))
     }
 
     fn get_tag_data(&self, tag: &str) -> Option<String> {
-        return self.data.get(tag).cloned();
+        self.data.get(tag).cloned()
     }
 
     fn get_all_tags(&self) -> Vec<String> {
@@ -53,12 +64,26 @@ impl TagDataRepository for HashMapRepository {
             .collect()
     }
 
-    fn add_tag_data(&mut self, tag: String, command: String) {
+    fn file_path(&self) -> Option<String> {
+        Some(self.file_path.clone())
+    }
+
+    fn add_tag_data(&mut self, tag: String, command: String) -> Result<(), RepoError> {
         self.data.insert(tag, command);
-        self.save().unwrap();
+        self.save()
+    }
+
+    fn remove_tag_data(&mut self, tag: &str) -> Result<String, RepoError> {
+        let command = self
+            .data
+            .remove(tag)
+            .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+        self.save()?;
+        Ok(command)
     }
 
-    fn remove_tag_data(&mut self, tag: &str) {
-        self.data.remove(tag);
+    fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), RepoError> {
+        self.data = data;
+        self.save()
     }
 }
@@ -0,0 +1,646 @@
+use super::cipher::Cipher;
+use super::error::RepoError;
+use super::tag_data_repository::TagDataRepository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const JOURNAL_SUFFIX: &str = ".journal";
+
+/// A change asked for, `Journal::apply` turns it into an `Operation`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// Adds `tag`, or replaces its command.
+    Add {
+        tag: String,
+        command: String,
+    },
+    Delete {
+        tag: String,
+    },
+    Rename {
+        tag: String,
+        new_tag: String,
+    },
+}
+
+/// A change as it was applied, with what it replaced so it can be undone.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add {
+        tag: String,
+        command: String,
+    },
+    Replace {
+        tag: String,
+        command: String,
+        previous: String,
+    },
+    Delete {
+        tag: String,
+        previous: String,
+    },
+    Rename {
+        tag: String,
+        new_tag: String,
+        command: String,
+    },
+}
+
+impl Operation {
+    /// What the operation did, without the commands it touched.
+    pub fn title(&self) -> String {
+        match self {
+            Operation::Add { tag, .. } => format!("add {}", tag),
+            Operation::Replace { tag, .. } => format!("replace {}", tag),
+            Operation::Delete { tag, .. } => format!("delete {}", tag),
+            Operation::Rename { tag, new_tag, .. } => format!("rename {} to {}", tag, new_tag),
+        }
+    }
+
+    // makes the change in `data`, as long as the tags it works on are there.
+    fn run<T: TagDataRepository + ?Sized>(
+        &self,
+        data: &mut HashMap<String, String>,
+        repo: &T,
+    ) -> Result<(), RepoError> {
+        match self {
+            Operation::Add { tag, command } | Operation::Replace { tag, command, .. } => {
+                data.insert(tag.clone(), command.clone());
+            }
+            Operation::Delete { tag, .. } => {
+                data.remove(tag).ok_or_else(|| repo.not_found(tag))?;
+            }
+            Operation::Rename {
+                tag,
+                new_tag,
+                command,
+            } => {
+                data.remove(tag).ok_or_else(|| repo.not_found(tag))?;
+                data.insert(new_tag.clone(), command.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn inverse(&self) -> Operation {
+        match self.clone() {
+            Operation::Add { tag, command } => Operation::Delete {
+                tag,
+                previous: command,
+            },
+            Operation::Replace {
+                tag,
+                command,
+                previous,
+            } => Operation::Replace {
+                tag,
+                command: previous,
+                previous: command,
+            },
+            Operation::Delete { tag, previous } => Operation::Add {
+                tag,
+                command: previous,
+            },
+            Operation::Rename {
+                tag,
+                new_tag,
+                command,
+            } => Operation::Rename {
+                tag: new_tag,
+                new_tag: tag,
+                command,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Add { tag, command } => write!(f, "add {}: {}", tag, command),
+            Operation::Replace {
+                tag,
+                command,
+                previous,
+            } => write!(f, "replace {}: {} (was: {})", tag, command, previous),
+            Operation::Delete { tag, previous } => write!(f, "delete {} (was: {})", tag, previous),
+            Operation::Rename { tag, new_tag, .. } => write!(f, "rename {} to {}", tag, new_tag),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Action {
+    /// Operations applied together, undone and redone together.
+    Apply {
+        ops: Vec<Operation>,
+    },
+    Undo {
+        target: u64,
+    },
+    Redo {
+        target: u64,
+    },
+}
+
+/// One line of the journal.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Entry {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl Entry {
+    pub fn ops(&self) -> &[Operation] {
+        match &self.action {
+            Action::Apply { ops } => ops,
+            _ => &[],
+        }
+    }
+
+    /// What the entry did, without the commands it touched.
+    pub fn title(&self) -> String {
+        match &self.action {
+            Action::Apply { ops } => ops
+                .iter()
+                .map(Operation::title)
+                .collect::<Vec<_>>()
+                .join(", "),
+            Action::Undo { target } => format!("undo #{}", target),
+            Action::Redo { target } => format!("redo #{}", target),
+        }
+    }
+}
+
+/// An append-only record of the changes to one store, kept next to its data file,
+/// with undo and redo over it. The journal of an encrypted store is encrypted line by line.
+pub struct Journal {
+    path: PathBuf,
+    cipher: Option<Cipher>,
+}
+
+/// The groups that undo and redo would take next, rebuilt from the whole journal.
+struct History {
+    applied: Vec<u64>,
+    undone: Vec<u64>,
+}
+
+impl Journal {
+    /// The journal of the store kept at `data_path`.
+    pub fn for_store(data_path: &str) -> Journal {
+        Journal {
+            path: PathBuf::from(format!("{}{}", data_path, JOURNAL_SUFFIX)),
+            cipher: None,
+        }
+    }
+
+    /// The same journal, its entries encrypted with `cipher` when there is one.
+    pub fn with_cipher(self, cipher: Option<Cipher>) -> Journal {
+        Journal { cipher, ..self }
+    }
+
+    /// Writes every entry again, encrypted with `cipher` or in plain without one,
+    /// and returns the journal that reads them.
+    pub fn rewrite(self, cipher: Option<Cipher>) -> Result<Journal, RepoError> {
+        let entries = self.entries()?;
+        let journal = self.with_cipher(cipher);
+        if !entries.is_empty() {
+            let text: String = entries
+                .iter()
+                .map(|entry| journal.line(entry) + "\n")
+                .collect();
+            super::replace_file(&journal.path.display().to_string(), &text)?;
+        }
+        Ok(journal)
+    }
+
+    fn line(&self, entry: &Entry) -> String {
+        // entries are plain data, they always serialize.
+        let json = serde_json::to_string(entry).unwrap();
+        match &self.cipher {
+            Some(cipher) => cipher.seal(json.as_bytes()),
+            None => json,
+        }
+    }
+
+    // a line in plain JSON starts with `{`, an encrypted one never does. An encrypted
+    // journal takes no plain lines, they could slip changes past the key.
+    fn parse(&self, line: &str) -> Result<Entry, RepoError> {
+        let path = self.path.display().to_string();
+        let json = match (&self.cipher, line.starts_with('{')) {
+            (Some(cipher), _) => cipher.open(line, &path)?,
+            (None, true) => line.as_bytes().to_vec(),
+            (None, false) => return Err(RepoError::Decrypt(path)),
+        };
+        serde_json::from_slice(&json).map_err(|source| RepoError::Parse { path, source })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every entry, oldest first.
+    pub fn entries(&self) -> Result<Vec<Entry>, RepoError> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(RepoError::io(&self.path.display().to_string(), e)),
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| self.parse(line))
+            .collect()
+    }
+
+    fn append(&self, action: Action) -> Result<Entry, RepoError> {
+        let io = |e| RepoError::io(&self.path.display().to_string(), e);
+        let entry = Entry {
+            id: self.entries()?.last().map_or(1, |last| last.id + 1),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            action,
+        };
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(io)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io)?;
+        writeln!(file, "{}", self.line(&entry)).map_err(io)?;
+        Ok(entry)
+    }
+
+    fn history(entries: &[Entry]) -> History {
+        let mut history = History {
+            applied: Vec::new(),
+            undone: Vec::new(),
+        };
+        for entry in entries {
+            match entry.action {
+                Action::Apply { .. } => {
+                    history.applied.push(entry.id);
+                    history.undone.clear();
+                }
+                Action::Undo { target } => {
+                    history.applied.retain(|id| *id != target);
+                    history.undone.push(target);
+                }
+                Action::Redo { target } => {
+                    history.undone.retain(|id| *id != target);
+                    history.applied.push(target);
+                }
+            }
+        }
+        history
+    }
+
+    /// Ids of the groups that were undone and not redone.
+    pub fn undone(&self) -> Result<Vec<u64>, RepoError> {
+        Ok(Self::history(&self.entries()?).undone)
+    }
+
+    /// Applies `changes` to the store `repo` changes go to as one group, saved once: all of
+    /// them or, when one fails, none.
+    pub fn apply<T: TagDataRepository + ?Sized>(
+        &self,
+        repo: &mut T,
+        changes: &[Change],
+    ) -> Result<Entry, RepoError> {
+        // planned against a copy of the store changes go to, saved once when all of them fit.
+        let mut data = repo.writable_data();
+        let before = data.clone();
+        let mut ops = Vec::new();
+        for change in changes {
+            let op = match change {
+                Change::Add { tag, command } => match data.get(tag) {
+                    Some(previous) => Operation::Replace {
+                        tag: tag.clone(),
+                        command: command.clone(),
+                        previous: previous.clone(),
+                    },
+                    None => Operation::Add {
+                        tag: tag.clone(),
+                        command: command.clone(),
+                    },
+                },
+                Change::Delete { tag } => Operation::Delete {
+                    tag: tag.clone(),
+                    previous: data.get(tag).cloned().ok_or_else(|| repo.not_found(tag))?,
+                },
+                Change::Rename { tag, new_tag } => {
+                    if data.contains_key(new_tag) {
+                        return Err(RepoError::AlreadyExists(new_tag.clone()));
+                    }
+                    Operation::Rename {
+                        tag: tag.clone(),
+                        new_tag: new_tag.clone(),
+                        command: data.get(tag).cloned().ok_or_else(|| repo.not_found(tag))?,
+                    }
+                }
+            };
+            op.run(&mut data, repo)?;
+            ops.push(op);
+        }
+        self.commit(repo, before, data, Action::Apply { ops })
+    }
+
+    // saves `data` as the store and records `action`, restoring `before` when the
+    // journal cannot be written so the two do not drift apart.
+    fn commit<T: TagDataRepository + ?Sized>(
+        &self,
+        repo: &mut T,
+        before: HashMap<String, String>,
+        data: HashMap<String, String>,
+        action: Action,
+    ) -> Result<Entry, RepoError> {
+        repo.replace_data(data)?;
+        self.append(action)
+            .map_err(|error| match repo.replace_data(before) {
+                Ok(()) => error,
+                Err(rollback) => RepoError::Rollback {
+                    path: repo.file_path().unwrap_or_default(),
+                    source: Box::new(error),
+                    rollback: Box::new(rollback),
+                },
+            })
+    }
+
+    // runs `ops` on a copy of the store changes go to and commits it as `action`.
+    fn replay<T: TagDataRepository + ?Sized>(
+        &self,
+        repo: &mut T,
+        ops: &[Operation],
+        action: Action,
+    ) -> Result<Entry, RepoError> {
+        let before = repo.writable_data();
+        let mut data = before.clone();
+        for op in ops {
+            op.run(&mut data, repo)?;
+        }
+        self.commit(repo, before, data, action)
+    }
+
+    /// Takes back the last group that is not undone yet, `None` when there is none.
+    pub fn undo<T: TagDataRepository + ?Sized>(
+        &self,
+        repo: &mut T,
+    ) -> Result<Option<Entry>, RepoError> {
+        let entries = self.entries()?;
+        let Some(target) = Self::history(&entries).applied.pop() else {
+            return Ok(None);
+        };
+        let group = entries
+            .into_iter()
+            .find(|entry| entry.id == target)
+            .unwrap();
+        let inverse: Vec<Operation> = group.ops().iter().rev().map(Operation::inverse).collect();
+        self.replay(repo, &inverse, Action::Undo { target })?;
+        Ok(Some(group))
+    }
+
+    /// Applies the last undone group again, `None` when there is none.
+    pub fn redo<T: TagDataRepository + ?Sized>(
+        &self,
+        repo: &mut T,
+    ) -> Result<Option<Entry>, RepoError> {
+        let entries = self.entries()?;
+        let Some(target) = Self::history(&entries).undone.pop() else {
+            return Ok(None);
+        };
+        let group = entries
+            .into_iter()
+            .find(|entry| entry.id == target)
+            .unwrap();
+        self.replay(repo, group.ops(), Action::Redo { target })?;
+        Ok(Some(group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::unittest_repository::{temporary_journal, UnitTestRepository};
+
+    fn add(tag: &str, command: &str) -> Change {
+        Change::Add {
+            tag: tag.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_records_previous_values() {
+        let journal = temporary_journal("apply");
+        let mut repo = UnitTestRepository::new();
+        journal.apply(&mut repo, &[add("a", "echo 1")]).unwrap();
+        journal
+            .apply(
+                &mut repo,
+                &[
+                    add("a", "echo 2"),
+                    Change::Rename {
+                        tag: "a".to_string(),
+                        new_tag: "b".to_string(),
+                    },
+                ],
+            )
+            .unwrap();
+        assert_eq!(repo.get_tag_data("a"), None);
+        assert_eq!(repo.get_tag_data("b").unwrap(), "echo 2");
+
+        let entries = journal.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[1].ops(),
+            &[
+                Operation::Replace {
+                    tag: "a".to_string(),
+                    command: "echo 2".to_string(),
+                    previous: "echo 1".to_string(),
+                },
+                Operation::Rename {
+                    tag: "a".to_string(),
+                    new_tag: "b".to_string(),
+                    command: "echo 2".to_string(),
+                },
+            ]
+        );
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_apply_is_all_or_nothing() {
+        let journal = temporary_journal("atomic");
+        let mut repo = UnitTestRepository::new();
+        let result = journal.apply(
+            &mut repo,
+            &[
+                add("a", "echo a"),
+                Change::Delete {
+                    tag: "missing".to_string(),
+                },
+            ],
+        );
+        assert!(matches!(result, Err(RepoError::NotFound(_))));
+        assert_eq!(repo.get_tag_data("a"), None);
+        assert!(journal.entries().unwrap().is_empty());
+
+        journal.apply(&mut repo, &[add("b", "echo b")]).unwrap();
+        let result = journal.apply(
+            &mut repo,
+            &[Change::Rename {
+                tag: "missing".to_string(),
+                new_tag: "b".to_string(),
+            }],
+        );
+        assert!(matches!(result, Err(RepoError::AlreadyExists(_))));
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let journal = temporary_journal("undo");
+        let mut repo = UnitTestRepository::new();
+        assert_eq!(journal.undo(&mut repo).unwrap(), None);
+
+        journal.apply(&mut repo, &[add("a", "echo 1")]).unwrap();
+        journal
+            .apply(
+                &mut repo,
+                &[
+                    add("a", "echo 2"),
+                    add("b", "echo b"),
+                    Change::Delete {
+                        tag: "b".to_string(),
+                    },
+                ],
+            )
+            .unwrap();
+
+        let undone = journal.undo(&mut repo).unwrap().unwrap();
+        assert_eq!(undone.id, 2);
+        assert_eq!(repo.get_tag_data("a").unwrap(), "echo 1");
+        assert_eq!(repo.get_tag_data("b"), None);
+        assert_eq!(journal.undone().unwrap(), vec![2]);
+
+        journal.undo(&mut repo).unwrap().unwrap();
+        assert_eq!(repo.get_tag_data("a"), None);
+        assert_eq!(journal.undo(&mut repo).unwrap(), None);
+
+        assert_eq!(journal.redo(&mut repo).unwrap().unwrap().id, 1);
+        assert_eq!(journal.redo(&mut repo).unwrap().unwrap().id, 2);
+        assert_eq!(repo.get_tag_data("a").unwrap(), "echo 2");
+        assert_eq!(journal.redo(&mut repo).unwrap(), None);
+
+        // a new change drops what could be redone.
+        journal.undo(&mut repo).unwrap();
+        journal.apply(&mut repo, &[add("c", "echo c")]).unwrap();
+        assert_eq!(journal.redo(&mut repo).unwrap(), None);
+        assert_eq!(journal.entries().unwrap().len(), 8);
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_journal() {
+        use crate::repo::cipher::test_params;
+        let cipher = Cipher::derive("p", b"0123456789abcdef", test_params()).unwrap();
+        let mut repo = UnitTestRepository::new();
+        let plain = temporary_journal("encrypted");
+        plain.apply(&mut repo, &[add("a", "TOKEN=s3cr3t")]).unwrap();
+
+        let journal = plain.rewrite(Some(cipher.clone())).unwrap();
+        journal.apply(&mut repo, &[add("b", "echo b")]).unwrap();
+        let text = fs::read_to_string(journal.path()).unwrap();
+        assert!(!text.contains("s3cr3t") && !text.contains("echo b"));
+        assert_eq!(journal.entries().unwrap().len(), 2);
+        journal.undo(&mut repo).unwrap().unwrap();
+        assert_eq!(repo.get_tag_data("b"), None);
+
+        // it is not read without the key or with another one.
+        let path = journal.path().to_path_buf();
+        let keyless = Journal {
+            path: path.clone(),
+            cipher: None,
+        };
+        assert!(matches!(keyless.entries(), Err(RepoError::Decrypt(_))));
+        let other = Cipher::derive("q", b"0123456789abcdef", test_params()).unwrap();
+        assert!(matches!(
+            journal.with_cipher(Some(other)).entries(),
+            Err(RepoError::Decrypt(_))
+        ));
+
+        let journal = Journal {
+            path: path.clone(),
+            cipher: Some(cipher),
+        };
+        let plain = journal.rewrite(None).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("s3cr3t"));
+        assert_eq!(plain.entries().unwrap().len(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_changes_only_the_write_layer() {
+        use crate::repo::layered_repository::{LayeredRepository, GLOBAL_LAYER, TEAM_LAYER};
+
+        let journal = temporary_journal("layers");
+        let mut team = UnitTestRepository::new();
+        team.add_tag_data("build".to_string(), "make".to_string())
+            .unwrap();
+        let mut repo = LayeredRepository::new();
+        repo.add_layer(TEAM_LAYER, Box::new(team));
+        repo.add_layer(GLOBAL_LAYER, Box::new(UnitTestRepository::new()));
+        repo.set_write_layer(GLOBAL_LAYER).unwrap();
+
+        let rename = Change::Rename {
+            tag: "build".to_string(),
+            new_tag: "compile".to_string(),
+        };
+        let result = journal.apply(&mut repo, &[rename]);
+        assert!(matches!(result, Err(RepoError::OtherLayer { layer, .. }) if layer == TEAM_LAYER));
+        assert!(repo.layer(GLOBAL_LAYER).unwrap().get_all_data().is_empty());
+        assert!(journal.entries().unwrap().is_empty());
+
+        // the team entry does not make an add in global a replace.
+        let entry = journal
+            .apply(&mut repo, &[add("build", "cargo build")])
+            .unwrap();
+        assert!(matches!(entry.ops(), [Operation::Add { .. }]));
+        journal.undo(&mut repo).unwrap();
+        assert!(repo.layer(GLOBAL_LAYER).unwrap().get_all_data().is_empty());
+        assert_eq!(repo.get_tag_data("build").unwrap(), "make");
+        fs::remove_file(journal.path()).unwrap();
+    }
+
+    #[test]
+    fn test_unwritable_journal_restores_the_store() {
+        let blocker = temporary_journal("blocker");
+        fs::write(blocker.path(), "").unwrap();
+        // the journal would go under a file, so it cannot be written.
+        let journal = Journal {
+            path: blocker.path().join("journal"),
+            cipher: None,
+        };
+        let mut repo = UnitTestRepository::new();
+        assert!(matches!(
+            journal.apply(&mut repo, &[add("a", "echo a")]),
+            Err(RepoError::Io { .. })
+        ));
+        assert_eq!(repo.get_tag_data("a"), None);
+        fs::remove_file(blocker.path()).unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use super::cipher::Cipher;
+use super::error::RepoError;
+use super::hashmap_repository::HashMapRepository;
+use super::journal::Journal;
+use super::tag_data_repository::TagDataRepository;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const GLOBAL_LAYER: &str = "global";
+pub const TEAM_LAYER: &str = "team";
+pub const PROJECT_LAYER: &str = "project";
+pub const PROJECT_FILE_NAME: &str = ".tagcm.json";
+
+struct Layer {
+    name: String,
+    repo: Box<dyn TagDataRepository>,
+}
+
+/// Stacks several stores, an entry in an earlier layer hides the same tag in later ones.
+/// Writes go to a single layer.
+pub struct LayeredRepository {
+    layers: Vec<Layer>,
+    write_layer: usize,
+}
+
+/// Looks for `.tagcm.json` in `start` and its ancestors.
+pub fn find_project_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+impl LayeredRepository {
+    /// Adds a layer below the existing ones.
+    pub fn add_layer(&mut self, name: &str, repo: Box<dyn TagDataRepository>) {
+        self.layers.push(Layer {
+            name: name.to_string(),
+            repo,
+        });
+    }
+
+    pub fn layer_names(&self) -> Vec<String> {
+        self.layers.iter().map(|layer| layer.name.clone()).collect()
+    }
+
+    fn position(&self, name: &str) -> Result<usize> {
+        self.layers
+            .iter()
+            .position(|layer| layer.name == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "layer {} is not configured, available layers: {}",
+                    name,
+                    self.layer_names().join(", ")
+                )
+            })
+    }
+
+    /// Name of the layer changes go to.
+    pub fn write_layer(&self) -> &str {
+        &self.layers[self.write_layer].name
+    }
+
+    pub fn set_write_layer(&mut self, name: &str) -> Result<()> {
+        self.write_layer = self.position(name)?;
+        Ok(())
+    }
+
+    /// The store of the layer `name`.
+    pub fn layer(&self, name: &str) -> Result<&dyn TagDataRepository> {
+        let index = self.position(name)?;
+        Ok(self.layers[index].repo.as_ref())
+    }
+
+    /// The store of the layer `name`, to change it whatever the write layer is.
+    pub fn layer_mut(&mut self, name: &str) -> Result<&mut dyn TagDataRepository> {
+        let index = self.position(name)?;
+        Ok(self.layers[index].repo.as_mut())
+    }
+
+    /// The journal of the layer `name`, every layer keeps its own.
+    pub fn journal(&self, name: &str) -> Result<Journal> {
+        let store = self.layer(name)?;
+        let path = store
+            .file_path()
+            .ok_or_else(|| anyhow!("the {} layer is not kept in a file", name))?;
+        Ok(Journal::for_store(&path).with_cipher(store.cipher()))
+    }
+
+    /// Name of the layer `tag` comes from, also when there is a single one.
+    pub fn layer_of(&self, tag: &str) -> Option<String> {
+        self.resolve(tag).map(|layer| layer.name.clone())
+    }
+
+    fn resolve(&self, tag: &str) -> Option<&Layer> {
+        self.layers
+            .iter()
+            .find(|layer| layer.repo.get_tag_data(tag).is_some())
+    }
+
+    fn writable(&mut self) -> &mut dyn TagDataRepository {
+        self.layers[self.write_layer].repo.as_mut()
+    }
+}
+
+impl TagDataRepository for LayeredRepository {
+    fn new() -> Self {
+        LayeredRepository {
+            layers: Vec::new(),
+            write_layer: 0,
+        }
+    }
+
+    /// Opens `file_path` as the global layer.
+    fn init(&mut self, file_path: &str) -> Result<(), RepoError> {
+        let mut repo = HashMapRepository::new();
+        repo.init(file_path)?;
+        self.add_layer(GLOBAL_LAYER, Box::new(repo));
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), RepoError> {
+        self.layers[self.write_layer].repo.save()
+    }
+
+    fn get_tag_data(&self, tag: &str) -> Option<String> {
+        self.resolve(tag)
+            .and_then(|layer| layer.repo.get_tag_data(tag))
+    }
+
+    fn get_all_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .layers
+            .iter()
+            .flat_map(|layer| layer.repo.get_all_tags())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    fn get_all_data(&self) -> Vec<(String, String)> {
+        self.get_all_tags()
+            .into_iter()
+            .filter_map(|tag| self.get_tag_data(&tag).map(|command| (tag, command)))
+            .collect()
+    }
+
+    fn get_origin(&self, tag: &str) -> Option<String> {
+        // a single store has nothing to tell apart.
+        if self.layers.len() < 2 {
+            return None;
+        }
+        self.resolve(tag).map(|layer| layer.name.clone())
+    }
+
+    fn file_path(&self) -> Option<String> {
+        self.layers[self.write_layer].repo.file_path()
+    }
+
+    fn cipher(&self) -> Option<Cipher> {
+        self.layers[self.write_layer].repo.cipher()
+    }
+
+    /// The entries of the write layer only, those of other layers are not changed.
+    fn writable_data(&self) -> HashMap<String, String> {
+        self.layers[self.write_layer].repo.writable_data()
+    }
+
+    fn not_found(&self, tag: &str) -> RepoError {
+        match self.resolve(tag) {
+            Some(layer) => RepoError::OtherLayer {
+                tag: tag.to_string(),
+                layer: layer.name.clone(),
+                write_layer: self.layers[self.write_layer].name.clone(),
+            },
+            None => RepoError::NotFound(tag.to_string()),
+        }
+    }
+
+    fn add_tag_data(&mut self, tag: String, command: String) -> Result<(), RepoError> {
+        self.writable().add_tag_data(tag, command)
+    }
+
+    fn remove_tag_data(&mut self, tag: &str) -> Result<String, RepoError> {
+        match self.writable().remove_tag_data(tag) {
+            Err(RepoError::NotFound(_)) => Err(self.not_found(tag)),
+            other => other,
+        }
+    }
+
+    fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), RepoError> {
+        self.writable().replace_data(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::unittest_repository::UnitTestRepository;
+
+    fn layer(entries: &[(&str, &str)]) -> Box<dyn TagDataRepository> {
+        let mut repo = UnitTestRepository::new();
+        for (tag, command) in entries {
+            repo.add_tag_data(tag.to_string(), command.to_string())
+                .unwrap();
+        }
+        Box::new(repo)
+    }
+
+    fn layered() -> LayeredRepository {
+        let mut repo = LayeredRepository::new();
+        repo.add_layer(PROJECT_LAYER, layer(&[("build", "cargo build")]));
+        repo.add_layer(
+            TEAM_LAYER,
+            layer(&[("build", "make"), ("deploy", "make deploy")]),
+        );
+        repo.add_layer(GLOBAL_LAYER, layer(&[("ls", "ls -la")]));
+        repo
+    }
+
+    #[test]
+    fn test_precedence() {
+        let repo = layered();
+        assert_eq!(repo.get_tag_data("build").unwrap(), "cargo build");
+        assert_eq!(repo.get_origin("build").unwrap(), PROJECT_LAYER);
+        assert_eq!(repo.get_origin("deploy").unwrap(), TEAM_LAYER);
+        assert_eq!(repo.get_origin("ls").unwrap(), GLOBAL_LAYER);
+        assert_eq!(repo.get_origin("missing"), None);
+        assert_eq!(
+            repo.get_all_data(),
+            vec![
+                ("build".to_string(), "cargo build".to_string()),
+                ("deploy".to_string(), "make deploy".to_string()),
+                ("ls".to_string(), "ls -la".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_layer() {
+        let mut repo = layered();
+        assert!(repo.set_write_layer("nothing").is_err());
+        repo.set_write_layer(GLOBAL_LAYER).unwrap();
+        repo.add_tag_data("deploy".to_string(), "echo shadowed".to_string())
+            .unwrap();
+        assert_eq!(repo.get_tag_data("deploy").unwrap(), "make deploy");
+
+        repo.set_write_layer(PROJECT_LAYER).unwrap();
+        assert_eq!(repo.remove_tag_data("build").unwrap(), "cargo build");
+        assert_eq!(repo.get_tag_data("build").unwrap(), "make");
+        assert_eq!(repo.get_origin("build").unwrap(), TEAM_LAYER);
+
+        assert!(matches!(
+            repo.remove_tag_data("build"),
+            Err(RepoError::OtherLayer { layer, .. }) if layer == TEAM_LAYER
+        ));
+        assert!(matches!(
+            repo.remove_tag_data("missing"),
+            Err(RepoError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_layer_mut() {
+        let mut repo = layered();
+        assert!(repo.layer_mut("nothing").is_err());
+        repo.layer_mut(GLOBAL_LAYER)
+            .unwrap()
+            .add_tag_data("build".to_string(), "echo global".to_string())
+            .unwrap();
+        assert_eq!(repo.layer_of("build").unwrap(), PROJECT_LAYER);
+        repo.layer_mut(PROJECT_LAYER)
+            .unwrap()
+            .remove_tag_data("build")
+            .unwrap();
+        repo.layer_mut(TEAM_LAYER)
+            .unwrap()
+            .remove_tag_data("build")
+            .unwrap();
+        assert_eq!(repo.layer_of("build").unwrap(), GLOBAL_LAYER);
+    }
+
+    #[test]
+    fn test_single_layer_has_no_origin() {
+        let mut repo = LayeredRepository::new();
+        repo.add_layer(GLOBAL_LAYER, layer(&[("ls", "ls -la")]));
+        assert_eq!(repo.get_origin("ls"), None);
+        assert_eq!(repo.layer_of("ls").unwrap(), GLOBAL_LAYER);
+    }
+
+    #[test]
+    fn test_find_project_file() {
+        let root = std::env::temp_dir().join(format!("tagcm_layers_{}", std::process::id()));
+        let nested = root.join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_project_file(&nested), None);
+
+        std::fs::write(root.join(PROJECT_FILE_NAME), "{}").unwrap();
+        assert_eq!(
+            find_project_file(&nested).unwrap(),
+            root.join(PROJECT_FILE_NAME)
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
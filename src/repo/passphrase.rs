@@ -0,0 +1,82 @@
+use super::error::RepoError;
+use crate::config::COMMAND_NAME;
+use directories::BaseDirs;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+
+pub const ENV_PASSPHRASE: &str = "TAGCM_PASSPHRASE";
+pub const ENV_AGENT_SOCKET: &str = "TAGCM_AGENT_SOCKET";
+const AGENT_SOCKET_NAME: &str = "agent.sock";
+
+/// Where the agent that keeps the passphrase listens, `TAGCM_AGENT_SOCKET` if set.
+pub fn agent_socket() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(ENV_AGENT_SOCKET) {
+        return Some(PathBuf::from(path));
+    }
+    BaseDirs::new().map(|base_dir| {
+        base_dir
+            .cache_dir()
+            .join(COMMAND_NAME)
+            .join(AGENT_SOCKET_NAME)
+    })
+}
+
+/// The passphrase the agent keeps, if one is running.
+#[cfg(unix)]
+pub fn from_agent() -> Option<String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(agent_socket()?).ok()?;
+    stream.write_all(b"get\n").ok()?;
+    let mut passphrase = String::new();
+    BufReader::new(stream).read_line(&mut passphrase).ok()?;
+    let passphrase = passphrase.trim_end_matches('\n');
+    (!passphrase.is_empty()).then(|| passphrase.to_string())
+}
+
+#[cfg(not(unix))]
+pub fn from_agent() -> Option<String> {
+    None
+}
+
+fn from_env() -> Option<String> {
+    env::var(ENV_PASSPHRASE).ok().filter(|p| !p.is_empty())
+}
+
+fn prompt(question: &str, path: &str) -> Result<String, RepoError> {
+    let passphrase = rpassword::prompt_password(question).map_err(|e| RepoError::io(path, e))?;
+    if passphrase.is_empty() {
+        return Err(RepoError::NoPassphrase(path.to_string()));
+    }
+    Ok(passphrase)
+}
+
+/// The passphrase of the encrypted store at `path`: `TAGCM_PASSPHRASE`, the one the agent
+/// keeps, or asked on the terminal.
+pub fn get(path: &str) -> Result<String, RepoError> {
+    if let Some(passphrase) = from_env().or_else(from_agent) {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(RepoError::NoPassphrase(path.to_string()));
+    }
+    prompt(&format!("passphrase for {}: ", path), path)
+}
+
+/// The passphrase to encrypt the store at `path` with: `TAGCM_PASSPHRASE`, or asked twice
+/// on the terminal so a typo does not lock the store.
+pub fn choose(path: &str) -> Result<String, RepoError> {
+    if let Some(passphrase) = from_env() {
+        return Ok(passphrase);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(RepoError::NoPassphrase(path.to_string()));
+    }
+    let passphrase = prompt(&format!("new passphrase for {}: ", path), path)?;
+    if prompt("repeat the passphrase: ", path)? != passphrase {
+        return Err(RepoError::PassphraseMismatch(path.to_string()));
+    }
+    Ok(passphrase)
+}
@@ -1,12 +1,48 @@
-use anyhow::Result;
+use super::cipher::Cipher;
+use super::error::RepoError;
+use std::collections::HashMap;
 
+/// A store of tags and the commands they stand for.
 pub trait TagDataRepository {
-    fn new() -> Self;
-    fn init(&mut self, file_path: &str) -> Result<()>;
-    fn save(&self) -> Result<()>;
+    /// An empty store, `init` loads it.
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// Loads the store from `file_path`, creating it when it does not exist yet.
+    fn init(&mut self, file_path: &str) -> Result<(), RepoError>;
+    /// Writes the store back to where `init` loaded it from.
+    fn save(&self) -> Result<(), RepoError>;
+    /// The command of `tag`.
     fn get_tag_data(&self, tag: &str) -> Option<String>;
     fn get_all_tags(&self) -> Vec<String>;
+    /// Every tag with its command, in no particular order.
     fn get_all_data(&self) -> Vec<(String, String)>;
-    fn add_tag_data(&mut self, tag: String, command: String);
-    fn remove_tag_data(&mut self, tag: &str);
+    /// Every tag with its command in the store changes go to, all of them unless the store
+    /// is made of several.
+    fn writable_data(&self) -> HashMap<String, String> {
+        self.get_all_data().into_iter().collect()
+    }
+    /// Why `tag` cannot be changed when the store changes go to does not have it.
+    fn not_found(&self, tag: &str) -> RepoError {
+        RepoError::NotFound(tag.to_string())
+    }
+    /// Name of the store the entry comes from, for stores made of several ones.
+    fn get_origin(&self, tag: &str) -> Option<String> {
+        _ = tag;
+        None
+    }
+    /// The file changes are written to, for stores kept in one.
+    fn file_path(&self) -> Option<String> {
+        None
+    }
+    /// The key the store is encrypted with, for stores that are.
+    fn cipher(&self) -> Option<Cipher> {
+        None
+    }
+    /// Adds or replaces `tag` and saves the store.
+    fn add_tag_data(&mut self, tag: String, command: String) -> Result<(), RepoError>;
+    /// Removes `tag` and saves the store, returns the command it had.
+    fn remove_tag_data(&mut self, tag: &str) -> Result<String, RepoError>;
+    /// Replaces every entry of the store changes go to with `data` and saves it once.
+    fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), RepoError>;
 }
@@ -1,5 +1,6 @@
-use super::tag_data_repository::TagDataRepository;
-use anyhow::{Ok, Result};
+use crate::repo::error::RepoError;
+use crate::repo::journal::Journal;
+use crate::repo::tag_data_repository::TagDataRepository;
 use std::collections::HashMap;
 
 pub struct UnitTestRepository {
@@ -13,17 +14,17 @@ impl TagDataRepository for UnitTestRepository {
         }
     }
 
-    fn init(&mut self, file_path: &str) -> Result<()> {
+    fn init(&mut self, file_path: &str) -> Result<(), RepoError> {
         _ = file_path;
         Ok(())
     }
 
-    fn save(&self) -> Result<()> {
+    fn save(&self) -> Result<(), RepoError> {
         Ok(())
     }
 
     fn get_tag_data(&self, tag: &str) -> Option<String> {
-        return self.data.get(tag).cloned();
+        self.data.get(tag).cloned()
     }
 
     fn get_all_tags(&self) -> Vec<String> {
@@ -37,12 +38,34 @@ impl TagDataRepository for UnitTestRepository {
             .collect()
     }
 
-    fn add_tag_data(&mut self, tag: String, command: String) {
+    fn add_tag_data(&mut self, tag: String, command: String) -> Result<(), RepoError> {
         self.data.insert(tag, command);
-        self.save().unwrap();
+        self.save()
     }
 
-    fn remove_tag_data(&mut self, tag: &str) {
-        self.data.remove(tag);
+    fn remove_tag_data(&mut self, tag: &str) -> Result<String, RepoError> {
+        let command = self
+            .data
+            .remove(tag)
+            .ok_or_else(|| RepoError::NotFound(tag.to_string()))?;
+        self.save()?;
+        Ok(command)
     }
+
+    fn replace_data(&mut self, data: HashMap<String, String>) -> Result<(), RepoError> {
+        self.data = data;
+        self.save()
+    }
+}
+
+/// An empty journal in the temp directory.
+pub fn temporary_journal(name: &str) -> Journal {
+    let path = std::env::temp_dir().join(format!(
+        "tagcm_journal_{}_{}.json",
+        name,
+        std::process::id()
+    ));
+    let journal = Journal::for_store(&path.display().to_string());
+    _ = std::fs::remove_file(journal.path());
+    journal
 }
